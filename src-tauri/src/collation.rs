@@ -0,0 +1,174 @@
+// 五十音順の照合順序（COLLATE KANA）。
+//
+// 一次比較では濁点・半濁点と小書きを無視して清音で並べ、同じになった場合だけ
+// 小書き < 清音 < 濁音 < 半濁音 の順で区別する（JIS X 4061 に近い並び）。
+// かな以外は 数字・英字 → かな → 漢字ほか の順に置く。
+use crate::normalize::{to_katakana, unify_width};
+use rusqlite::Connection;
+use std::cmp::Ordering;
+
+pub const KANA_COLLATION: &str = "KANA";
+
+/// 清音ごとのグループ。(文字, 区別) で、区別は 0: 小書き, 1: 清音, 2: 濁音, 3: 半濁音。
+const KANA_GROUPS: &[&[(char, u8)]] = &[
+    &[('ァ', 0), ('ア', 1)],
+    &[('ィ', 0), ('イ', 1)],
+    &[('ゥ', 0), ('ウ', 1), ('ヴ', 2)],
+    &[('ェ', 0), ('エ', 1)],
+    &[('ォ', 0), ('オ', 1)],
+    &[('ヵ', 0), ('カ', 1), ('ガ', 2)],
+    &[('キ', 1), ('ギ', 2)],
+    &[('ク', 1), ('グ', 2)],
+    &[('ヶ', 0), ('ケ', 1), ('ゲ', 2)],
+    &[('コ', 1), ('ゴ', 2)],
+    &[('サ', 1), ('ザ', 2)],
+    &[('シ', 1), ('ジ', 2)],
+    &[('ス', 1), ('ズ', 2)],
+    &[('セ', 1), ('ゼ', 2)],
+    &[('ソ', 1), ('ゾ', 2)],
+    &[('タ', 1), ('ダ', 2)],
+    &[('チ', 1), ('ヂ', 2)],
+    &[('ッ', 0), ('ツ', 1), ('ヅ', 2)],
+    &[('テ', 1), ('デ', 2)],
+    &[('ト', 1), ('ド', 2)],
+    &[('ナ', 1)],
+    &[('ニ', 1)],
+    &[('ヌ', 1)],
+    &[('ネ', 1)],
+    &[('ノ', 1)],
+    &[('ハ', 1), ('バ', 2), ('パ', 3)],
+    &[('ヒ', 1), ('ビ', 2), ('ピ', 3)],
+    &[('フ', 1), ('ブ', 2), ('プ', 3)],
+    &[('ヘ', 1), ('ベ', 2), ('ペ', 3)],
+    &[('ホ', 1), ('ボ', 2), ('ポ', 3)],
+    &[('マ', 1)],
+    &[('ミ', 1)],
+    &[('ム', 1)],
+    &[('メ', 1)],
+    &[('モ', 1)],
+    &[('ャ', 0), ('ヤ', 1)],
+    &[('ュ', 0), ('ユ', 1)],
+    &[('ョ', 0), ('ヨ', 1)],
+    &[('ラ', 1)],
+    &[('リ', 1)],
+    &[('ル', 1)],
+    &[('レ', 1)],
+    &[('ロ', 1)],
+    &[('ヮ', 0), ('ワ', 1), ('ヷ', 2)],
+    &[('ヰ', 1), ('ヸ', 2)],
+    &[('ヱ', 1), ('ヹ', 2)],
+    &[('ヲ', 1), ('ヺ', 2)],
+    &[('ン', 1)],
+];
+
+/// 段ごとの清音（長音 "ー" を直前の母音として扱うため）
+const VOWEL_ROWS: [&str; 5] = [
+    "アカサタナハマヤラワ",
+    "イキシチニヒミリヰ",
+    "ウクスツヌフムユル",
+    "エケセテネヘメレヱ",
+    "オコソトノホモヨロヲ",
+];
+
+const CLASS_LATIN: u8 = 0;
+const CLASS_KANA: u8 = 1;
+const CLASS_OTHER: u8 = 2;
+
+/// 接続に KANA 照合順序を登録する。
+pub fn register(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.create_collation(KANA_COLLATION, compare_kana)
+}
+
+pub fn compare_kana(a: &str, b: &str) -> Ordering {
+    let (a_primary, a_secondary) = sort_key(a);
+    let (b_primary, b_secondary) = sort_key(b);
+    a_primary
+        .cmp(&b_primary)
+        .then_with(|| a_secondary.cmp(&b_secondary))
+        .then_with(|| a.cmp(b))
+}
+
+fn sort_key(s: &str) -> (Vec<(u8, u32)>, Vec<u8>) {
+    let text = to_katakana(&unify_width(s));
+    let mut primary = Vec::new();
+    let mut secondary = Vec::new();
+    let mut last_group: Option<usize> = None;
+
+    for c in text.chars() {
+        if c.is_whitespace() || c == '・' {
+            continue;
+        }
+        if c == 'ー' {
+            if let Some(vowel) = last_group.and_then(vowel_group) {
+                primary.push((CLASS_KANA, vowel as u32));
+                secondary.push(4);
+                continue;
+            }
+        }
+        if let Some((group, variant)) = kana_group(c) {
+            primary.push((CLASS_KANA, group as u32));
+            secondary.push(variant);
+            last_group = Some(group);
+            continue;
+        }
+        last_group = None;
+        if c.is_ascii_alphanumeric() {
+            primary.push((CLASS_LATIN, c.to_ascii_lowercase() as u32));
+            secondary.push(u8::from(c.is_ascii_uppercase()));
+        } else {
+            primary.push((CLASS_OTHER, c as u32));
+            secondary.push(0);
+        }
+    }
+    (primary, secondary)
+}
+
+fn kana_group(c: char) -> Option<(usize, u8)> {
+    KANA_GROUPS.iter().enumerate().find_map(|(index, group)| {
+        group
+            .iter()
+            .find(|(kana, _)| *kana == c)
+            .map(|(_, variant)| (index, *variant))
+    })
+}
+
+fn vowel_group(group: usize) -> Option<usize> {
+    let (base, _) = KANA_GROUPS[group].iter().find(|(_, variant)| *variant == 1)?;
+    let row = VOWEL_ROWS.iter().position(|row| row.contains(*base))?;
+    let vowel = ['ア', 'イ', 'ウ', 'エ', 'オ'][row];
+    kana_group(vowel).map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(words: &[&str]) -> Vec<String> {
+        let mut words: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+        words.sort_by(|a, b| compare_kana(a, b));
+        words
+    }
+
+    #[test]
+    fn orders_by_gojuon_ignoring_script() {
+        assert_eq!(sorted(&["さくら", "アカシア", "かえで"]), ["アカシア", "かえで", "さくら"]);
+    }
+
+    #[test]
+    fn voiced_and_small_kana_follow_their_base() {
+        assert_eq!(sorted(&["ハン", "パン", "バン"]), ["ハン", "バン", "パン"]);
+        assert_eq!(sorted(&["ガス", "カス", "カズ"]), ["カス", "カズ", "ガス"]);
+        assert_eq!(sorted(&["キヨ", "キョ"]), ["キョ", "キヨ"]);
+    }
+
+    #[test]
+    fn long_vowel_sorts_as_preceding_vowel() {
+        // "カード" は "カアド" と同じ位置に来る
+        assert_eq!(sorted(&["カイ", "カード", "カア"]), ["カア", "カード", "カイ"]);
+    }
+
+    #[test]
+    fn latin_before_kana_before_kanji() {
+        assert_eq!(sorted(&["漢字", "あ", "ABC", "1"]), ["1", "ABC", "あ", "漢字"]);
+    }
+}
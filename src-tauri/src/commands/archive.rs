@@ -0,0 +1,662 @@
+use crate::collation;
+use crate::commands::backup::validate_backup;
+use crate::commands::cover::{cover_dir, covers_dir};
+use crate::db::{backup_database, data_dir, migrate, schema_version, DbConnection};
+use crate::error::CommandError;
+use crate::history::{record_change, snapshot_book};
+use crate::models::{ArchiveManifest, HistoryAction, ImportMode, ImportResult};
+use crate::normalize::normalize_isbn;
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, DatabaseName, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tauri::State;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const ARCHIVE_FORMAT: &str = "bibly-archive";
+/// アーカイブの形式のバージョン。zip の中身の構成を変えたら上げる。
+const ARCHIVE_FORMAT_VERSION: i64 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "library.sqlite";
+const SETTINGS_ENTRY: &str = "settings.json";
+const COVERS_ENTRY: &str = "covers";
+
+/// ライブラリ一式（データベース・書影・設定）を zip にまとめて `path` に書き出す。
+/// `settings` にはフロントエンドの localStorage の内容を渡す（API キーは頼まれたときだけ含まれる）。
+#[tauri::command]
+pub fn export_library_archive(
+    path: String,
+    settings: HashMap<String, String>,
+    db: State<DbConnection>,
+) -> Result<ArchiveManifest, String> {
+    let snapshot = data_dir().join("archive-export.sqlite");
+    let manifest = {
+        let conn = db.0.lock().unwrap();
+        conn.backup(DatabaseName::Main, &snapshot, None)
+            .map_err(|e| e.to_string())?;
+        let (exported_at, book_count): (String, i64) = conn
+            .query_row(
+                "SELECT datetime('now', 'localtime'),
+                        (SELECT COUNT(*) FROM books WHERE deleted_at IS NULL)",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        ArchiveManifest {
+            format: ARCHIVE_FORMAT.to_string(),
+            format_version: ARCHIVE_FORMAT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version: schema_version(),
+            exported_at,
+            book_count,
+            cover_count: 0,
+        }
+    };
+    let result = write_archive(Path::new(&path), manifest, &snapshot, &settings);
+    let _ = fs::remove_file(&snapshot);
+    result.map_err(|e| format!("アーカイブを書き出せませんでした: {}", e))
+}
+
+/// アーカイブを取り込む。形式とスキーマのバージョンを確かめてから、
+/// `replace` なら今のライブラリを置き換え、`merge` なら無い本だけを追加する。
+/// どちらの場合も、取り込む前の状態をバックアップに残す。
+#[tauri::command]
+pub fn import_library_archive(
+    path: String,
+    mode: ImportMode,
+    db: State<DbConnection>,
+) -> Result<ImportResult, CommandError> {
+    let file = File::open(&path)
+        .map_err(|e| CommandError::NotFound(format!("アーカイブを開けませんでした: {}", e)))?;
+    let mut archive = ZipArchive::new(file).map_err(|_| not_an_archive())?;
+    let manifest = read_manifest(&mut archive)?;
+    let settings: HashMap<String, String> = match archive.by_name(SETTINGS_ENTRY) {
+        Ok(entry) => serde_json::from_reader(entry).map_err(|_| not_an_archive())?,
+        Err(_) => HashMap::new(),
+    };
+
+    let staged_path = data_dir().join("archive-import.sqlite");
+    let result = extract_entry(&mut archive, DATABASE_ENTRY, &staged_path)
+        .and_then(|_| import_database(&staged_path, mode, &db))
+        .and_then(|(books, books_skipped)| {
+            let covers_imported = import_covers(&mut archive, mode, &books)
+                .map_err(|e| CommandError::Database(format!("書影を取り込めませんでした: {}", e)))?;
+            Ok(ImportResult {
+                manifest,
+                books_added: books.len() as i64,
+                books_skipped,
+                covers_imported,
+                settings,
+            })
+        });
+    let _ = fs::remove_file(&staged_path);
+    result
+}
+
+fn write_archive(
+    path: &Path,
+    mut manifest: ArchiveManifest,
+    snapshot: &Path,
+    settings: &HashMap<String, String>,
+) -> io::Result<ArchiveManifest> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    // 画像は圧縮済みなのでそのまま入れる
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    zip.start_file(DATABASE_ENTRY, deflated)?;
+    io::copy(&mut File::open(snapshot)?, &mut zip)?;
+    zip.start_file(SETTINGS_ENTRY, deflated)?;
+    serde_json::to_writer_pretty(&mut zip, settings)?;
+
+    let covers = covers_dir();
+    if covers.exists() {
+        for book_dir in fs::read_dir(&covers)?.filter_map(|entry| entry.ok()) {
+            let Some(book_id) = book_dir.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if book_id.parse::<i64>().is_err() || !book_dir.path().is_dir() {
+                continue;
+            }
+            for image in fs::read_dir(book_dir.path())?.filter_map(|entry| entry.ok()) {
+                let Some(file_name) = image.file_name().to_str().map(str::to_string) else {
+                    continue;
+                };
+                zip.start_file(format!("{}/{}/{}", COVERS_ENTRY, book_id, file_name), stored)?;
+                io::copy(&mut File::open(image.path())?, &mut zip)?;
+            }
+            manifest.cover_count += 1;
+        }
+    }
+
+    // 中身を書き終えてから件数の入った manifest を足す
+    zip.start_file(MANIFEST_ENTRY, deflated)?;
+    serde_json::to_writer_pretty(&mut zip, &manifest)?;
+    zip.finish()?;
+    Ok(manifest)
+}
+
+fn read_manifest(archive: &mut ZipArchive<File>) -> Result<ArchiveManifest, CommandError> {
+    let entry = archive.by_name(MANIFEST_ENTRY).map_err(|_| not_an_archive())?;
+    let manifest: ArchiveManifest = serde_json::from_reader(entry).map_err(|_| not_an_archive())?;
+    if manifest.format != ARCHIVE_FORMAT {
+        return Err(not_an_archive());
+    }
+    if manifest.format_version > ARCHIVE_FORMAT_VERSION || manifest.schema_version > schema_version() {
+        return Err(CommandError::Invalid(format!(
+            "新しいバージョンのアプリ（{}）で作られたアーカイブです",
+            manifest.app_version
+        )));
+    }
+    Ok(manifest)
+}
+
+fn not_an_archive() -> CommandError {
+    CommandError::Invalid("bibly のアーカイブではありません".into())
+}
+
+fn extract_entry(archive: &mut ZipArchive<File>, name: &str, to: &Path) -> Result<(), CommandError> {
+    let mut entry = archive.by_name(name).map_err(|_| not_an_archive())?;
+    let mut file = File::create(to).map_err(|e| CommandError::Database(e.to_string()))?;
+    io::copy(&mut entry, &mut file).map_err(|e| CommandError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// 取り出したデータベースを今のスキーマに揃えてから取り込む。
+/// 追加した本の（アーカイブでの id, 取り込み後の id）と、追加しなかった本の数を返す。
+fn import_database(
+    staged_path: &Path,
+    mode: ImportMode,
+    db: &DbConnection,
+) -> Result<(Vec<(i64, i64)>, i64), CommandError> {
+    let mut staged = Connection::open(staged_path)?;
+    collation::register(&staged)?;
+    validate_backup(&staged)?;
+    migrate(&mut staged)?;
+
+    let mut conn = db.0.lock().unwrap();
+    backup_database(&conn).map_err(CommandError::Database)?;
+    match mode {
+        ImportMode::Replace => {
+            let mut stmt = staged.prepare("SELECT id FROM books ORDER BY id")?;
+            let books = stmt
+                .query_map([], |row| row.get(0))?
+                .map(|id| id.map(|id| (id, id)))
+                .collect::<Result<Vec<_>, _>>()?;
+            drop(stmt);
+            Backup::new(&staged, &mut conn)?.run_to_completion(256, Duration::ZERO, None)?;
+            Ok((books, 0))
+        }
+        ImportMode::Merge => {
+            staged.close().map_err(|(_, e)| e)?;
+            conn.execute(
+                "ATTACH DATABASE ?1 AS archive",
+                [staged_path.to_string_lossy()],
+            )?;
+            let result = (|| {
+                let tx = conn.transaction()?;
+                let merged = merge_archive(&tx)?;
+                tx.commit()?;
+                Ok(merged)
+            })();
+            conn.execute_batch("DETACH DATABASE archive")?;
+            result
+        }
+    }
+}
+
+/// attach した `archive` の内容を main に足す。ジャンル・タグ・シリーズ・置き場所は名前で
+/// 対応づけ、無ければ作る。本は ISBN（無ければ書名と著者）が同じものが既にあれば追加せず、
+/// タグだけを引き継ぐ。新しく追加した本には冊・貸出・読書記録・メモ・シリーズを付ける。
+fn merge_archive(conn: &Connection) -> Result<(Vec<(i64, i64)>, i64), rusqlite::Error> {
+    conn.execute_batch(
+        "CREATE TEMP TABLE archive_ids (
+            kind    TEXT NOT NULL,
+            old_id  INTEGER NOT NULL,
+            new_id  INTEGER NOT NULL,
+            PRIMARY KEY (kind, old_id)
+         );",
+    )?;
+
+    let first_new_genre: i64 =
+        conn.query_row("SELECT IFNULL(MAX(id), 0) + 1 FROM main.genres", [], |row| row.get(0))?;
+    copy_rows(
+        conn,
+        "INSERT",
+        "genres",
+        &[("parent_id", "NULL".to_string())],
+        "src.name NOT IN (SELECT name FROM main.genres)",
+        [],
+    )?;
+    conn.execute_batch(
+        "INSERT INTO archive_ids
+            SELECT 'genre', a.id, m.id FROM archive.genres a JOIN main.genres m ON m.name = a.name;",
+    )?;
+    // 新しく作ったジャンルだけ、アーカイブでの親子関係を付け直す
+    conn.execute(
+        &format!(
+            "UPDATE main.genres SET parent_id = (
+                SELECT {} FROM archive.genres src WHERE src.name = genres.name
+             ) WHERE id >= ?1",
+            mapped("genre", "src.parent_id")
+        ),
+        [first_new_genre],
+    )?;
+
+    copy_rows(
+        conn,
+        "INSERT",
+        "tags",
+        &[],
+        "src.name NOT IN (SELECT name FROM main.tags)",
+        [],
+    )?;
+    conn.execute_batch(
+        "INSERT INTO archive_ids
+            SELECT 'tag', a.id, m.id FROM archive.tags a JOIN main.tags m ON m.name = a.name;",
+    )?;
+    copy_rows(
+        conn,
+        "INSERT",
+        "series",
+        &[],
+        "src.title NOT IN (SELECT title FROM main.series)",
+        [],
+    )?;
+    conn.execute_batch(
+        "INSERT INTO archive_ids
+            SELECT 'series', a.id, m.id FROM archive.series a JOIN main.series m ON m.title = a.title;",
+    )?;
+    merge_locations(conn)?;
+
+    let (books, skipped) = merge_books(conn)?;
+    let new_books = "src.book_id IN (SELECT old_id FROM archive_ids WHERE kind = 'new_book')";
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT src.id FROM archive.copies src WHERE {} ORDER BY src.id",
+        new_books
+    ))?;
+    let copy_ids = stmt
+        .query_map([], |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+    for old_id in copy_ids {
+        copy_rows(
+            conn,
+            "INSERT",
+            "copies",
+            &[
+                ("book_id", mapped("book", "src.book_id")),
+                ("location_id", mapped("location", "src.location_id")),
+            ],
+            "src.id = ?1",
+            [old_id],
+        )?;
+        record_id(conn, "copy", old_id, conn.last_insert_rowid())?;
+    }
+    let book_id = [("book_id", mapped("book", "src.book_id"))];
+    copy_rows(
+        conn,
+        "INSERT",
+        "loans",
+        &[
+            ("book_id", mapped("book", "src.book_id")),
+            ("copy_id", mapped("copy", "src.copy_id")),
+        ],
+        new_books,
+        [],
+    )?;
+    copy_rows(conn, "INSERT", "reading_sessions", &book_id, new_books, [])?;
+    copy_rows(conn, "INSERT", "book_notes", &book_id, new_books, [])?;
+    copy_rows(
+        conn,
+        "INSERT OR IGNORE",
+        "book_series",
+        &[
+            ("book_id", mapped("book", "src.book_id")),
+            ("series_id", mapped("series", "src.series_id")),
+        ],
+        new_books,
+        [],
+    )?;
+    copy_rows(
+        conn,
+        "INSERT OR IGNORE",
+        "book_tags",
+        &[
+            ("book_id", mapped("book", "src.book_id")),
+            ("tag_id", mapped("tag", "src.tag_id")),
+        ],
+        "src.book_id IN (SELECT old_id FROM archive_ids WHERE kind = 'book')",
+        [],
+    )?;
+    copy_rows(
+        conn,
+        "INSERT OR IGNORE",
+        "wishlist",
+        &[],
+        "NOT EXISTS (SELECT 1 FROM main.wishlist w WHERE w.title = src.title AND w.isbn IS src.isbn)",
+        [],
+    )?;
+
+    for &(_, book_id) in &books {
+        let after = snapshot_book(conn, book_id)?;
+        record_change(conn, book_id, HistoryAction::Insert, None, after.as_ref())?;
+    }
+    conn.execute_batch("DROP TABLE temp.archive_ids;")?;
+    Ok((books, skipped))
+}
+
+/// 置き場所は親ごとに名前で対応づけるので、親から順にたどる。
+fn merge_locations(conn: &Connection) -> Result<(), rusqlite::Error> {
+    let mut stmt = conn.prepare("SELECT id, name, parent_id FROM archive.locations ORDER BY id")?;
+    let mut pending = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<i64>>(2)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+
+    let mut ids: HashMap<i64, i64> = HashMap::new();
+    while !pending.is_empty() {
+        let before = pending.len();
+        let mut rest = Vec::new();
+        for (old_id, name, old_parent) in pending {
+            let parent = match old_parent {
+                Some(old_parent) => match ids.get(&old_parent) {
+                    Some(&parent) => Some(parent),
+                    None => {
+                        rest.push((old_id, name, Some(old_parent)));
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            let existing: Option<i64> = conn
+                .query_row(
+                    "SELECT id FROM main.locations WHERE name = ?1 AND parent_id IS ?2",
+                    rusqlite::params![name, parent],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let new_id = match existing {
+                Some(id) => id,
+                None => {
+                    copy_rows(
+                        conn,
+                        "INSERT",
+                        "locations",
+                        &[("parent_id", mapped("location", "src.parent_id"))],
+                        "src.id = ?1",
+                        [old_id],
+                    )?;
+                    conn.last_insert_rowid()
+                }
+            };
+            record_id(conn, "location", old_id, new_id)?;
+            ids.insert(old_id, new_id);
+        }
+        // 親がたどれない（壊れた）置き場所は取り込まない
+        if rest.len() == before {
+            break;
+        }
+        pending = rest;
+    }
+    Ok(())
+}
+
+/// 本を対応づけ、無い本だけを追加する。追加した本の（旧 id, 新 id）と追加しなかった数を返す。
+/// ゴミ箱の本は無いものとして扱い、アーカイブの中で重なる本は最初の1冊だけを追加する。
+fn merge_books(conn: &Connection) -> Result<(Vec<(i64, i64)>, i64), rusqlite::Error> {
+    let mut by_isbn: HashMap<String, i64> = HashMap::new();
+    let mut by_title: HashMap<(String, Option<String>), i64> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT id, isbn, title, author FROM main.books WHERE deleted_at IS NULL")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        match row.get::<_, Option<String>>(1)?.as_deref().and_then(normalize_isbn) {
+            Some(isbn) => {
+                by_isbn.entry(isbn).or_insert(id);
+            }
+            None => {
+                by_title.entry((row.get(2)?, row.get(3)?)).or_insert(id);
+            }
+        }
+    }
+    drop(rows);
+    drop(stmt);
+
+    let mut stmt = conn.prepare("SELECT id, isbn, title, author FROM archive.books ORDER BY id")?;
+    let archived = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+
+    let mut added = Vec::new();
+    let mut skipped = 0;
+    for (old_id, isbn, title, author) in archived {
+        let isbn = isbn.as_deref().and_then(normalize_isbn);
+        let existing = match &isbn {
+            Some(isbn) => by_isbn.get(isbn),
+            None => by_title.get(&(title.clone(), author.clone())),
+        };
+        if let Some(&id) = existing {
+            record_id(conn, "book", old_id, id)?;
+            skipped += 1;
+            continue;
+        }
+        copy_rows(
+            conn,
+            "INSERT",
+            "books",
+            &[("genre_id", mapped("genre", "src.genre_id"))],
+            "src.id = ?1",
+            [old_id],
+        )?;
+        let new_id = conn.last_insert_rowid();
+        record_id(conn, "book", old_id, new_id)?;
+        record_id(conn, "new_book", old_id, new_id)?;
+        match isbn {
+            Some(isbn) => by_isbn.insert(isbn, new_id),
+            None => by_title.insert((title, author), new_id),
+        };
+        added.push((old_id, new_id));
+    }
+    Ok((added, skipped))
+}
+
+/// `archive.{table}` の行を `main.{table}` に写す。id は振り直し、
+/// `overrides` に挙げた列は `src` を参照する SQL 式の値に置き換える。
+fn copy_rows(
+    conn: &Connection,
+    insert: &str,
+    table: &str,
+    overrides: &[(&str, String)],
+    condition: &str,
+    params: impl rusqlite::Params,
+) -> Result<usize, rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!("PRAGMA main.table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let columns: Vec<String> = columns.into_iter().filter(|c| c != "id").collect();
+    let values: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            overrides
+                .iter()
+                .find(|(name, _)| name == column)
+                .map(|(_, expr)| expr.clone())
+                .unwrap_or_else(|| format!("src.{}", column))
+        })
+        .collect();
+    conn.execute(
+        &format!(
+            "{} INTO main.{table} ({}) SELECT {} FROM archive.{table} src WHERE {}",
+            insert,
+            columns.join(", "),
+            values.join(", "),
+            condition,
+            table = table
+        ),
+        params,
+    )
+}
+
+/// アーカイブでの id を取り込み後の id に置き換える SQL 式
+fn mapped(kind: &str, old_id: &str) -> String {
+    format!(
+        "(SELECT new_id FROM archive_ids WHERE kind = '{}' AND old_id = {})",
+        kind, old_id
+    )
+}
+
+fn record_id(conn: &Connection, kind: &str, old_id: i64, new_id: i64) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "INSERT OR REPLACE INTO archive_ids (kind, old_id, new_id) VALUES (?1, ?2, ?3)",
+        rusqlite::params![kind, old_id, new_id],
+    )?;
+    Ok(())
+}
+
+/// `covers/{book_id}/...` を取り出す。replace なら今の書影を全部消してから、
+/// merge なら新しく追加した本の分だけを取り込み後の id に置く。取り込んだ本の数を返す。
+fn import_covers(
+    archive: &mut ZipArchive<File>,
+    mode: ImportMode,
+    books: &[(i64, i64)],
+) -> io::Result<i64> {
+    if mode == ImportMode::Replace && covers_dir().exists() {
+        fs::remove_dir_all(covers_dir())?;
+    }
+    let new_ids: HashMap<i64, i64> = books.iter().copied().collect();
+    let mut imported = HashSet::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let Some((old_id, file_name)) = entry.enclosed_name().as_deref().and_then(cover_entry) else {
+            continue;
+        };
+        let Some(&book_id) = new_ids.get(&old_id) else {
+            continue;
+        };
+        let dir = cover_dir(book_id);
+        fs::create_dir_all(&dir)?;
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        File::create(dir.join(file_name))?.write_all(&bytes)?;
+        imported.insert(book_id);
+    }
+    Ok(imported.len() as i64)
+}
+
+/// `covers/{book_id}/{file_name}` なら (book_id, file_name)
+fn cover_entry(path: &Path) -> Option<(i64, PathBuf)> {
+    let mut components = path.components().map(|c| match c {
+        Component::Normal(name) => name.to_str(),
+        _ => None,
+    });
+    if components.next()?? != COVERS_ENTRY {
+        return None;
+    }
+    let book_id = components.next()??.parse().ok()?;
+    let file_name = components.next()??;
+    if components.next().is_some() {
+        return None;
+    }
+    Some((book_id, PathBuf::from(file_name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::open_in_memory;
+
+    fn query_ids(conn: &Connection, sql: &str, id: i64) -> Vec<i64> {
+        conn.prepare(sql)
+            .unwrap()
+            .query_map([id], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn id_of(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn merge_archive_adds_missing_books_with_remapped_ids() {
+        let conn = open_in_memory("merge_main");
+        conn.execute_batch(
+            "INSERT INTO books (id, isbn, title) VALUES (1, '9784101010014', '坊っちゃん');
+             INSERT INTO books (id, isbn, title, deleted_at) VALUES (2, '9784003101018', '羅生門', '2024-01-01');
+             INSERT INTO books (id, title, author) VALUES (10, 'こころ', '夏目漱石');
+             INSERT INTO tags (id, name) VALUES (1, 'お気に入り');
+             INSERT INTO locations (id, name, kind) VALUES (1, '書斎', 'building');",
+        )
+        .unwrap();
+        // アーカイブ側は id の振り方が違う。接続はマージが終わるまで開いておく
+        let archive = open_in_memory("merge_archive");
+        archive
+            .execute_batch(
+                "INSERT INTO genres (id, name) VALUES (1, '小説');
+                 INSERT INTO tags (id, name) VALUES (1, '再読'), (2, 'お気に入り');
+                 INSERT INTO series (id, title) VALUES (1, '漱石全集');
+                 INSERT INTO locations (id, name, kind, parent_id)
+                     VALUES (1, '書斎', 'building', NULL), (2, '二階', 'room', 1);
+                 INSERT INTO books (id, isbn, title, author, genre_id) VALUES
+                     (1, '978-4-10-101001-4', '坊っちゃん', NULL, NULL),
+                     (2, '9784003101018', '羅生門', NULL, 1),
+                     (3, '4003101014', '羅生門', NULL, NULL),
+                     (4, NULL, 'こころ', '夏目漱石', NULL),
+                     (5, NULL, '三四郎', '夏目漱石', NULL);
+                 INSERT INTO book_tags (book_id, tag_id) VALUES (1, 1), (2, 2);
+                 INSERT INTO book_series (book_id, series_id, volume) VALUES (5, 1, 3);
+                 INSERT INTO copies (id, book_id, location_id) VALUES (1, 2, 2), (2, 5, 1), (3, 1, NULL);
+                 INSERT INTO loans (book_id, copy_id, borrower, lent_at) VALUES (2, 1, '山田', '2024-01-01');",
+            )
+            .unwrap();
+        conn.execute_batch("ATTACH DATABASE 'file:merge_archive?mode=memory&cache=shared' AS archive")
+            .unwrap();
+
+        let (books, skipped) = merge_archive(&conn).unwrap();
+        // 1 は ISBN の表記違い、3 は 2 と同じ本、4 は書名と著者が同じ本
+        assert_eq!(skipped, 3);
+        assert_eq!(books.iter().map(|&(old, _)| old).collect::<Vec<_>>(), [2, 5]);
+        let (rashomon, sanshiro) = (books[0].1, books[1].1);
+        assert!(rashomon > 10 && sanshiro > 10);
+
+        let novel = id_of(&conn, "SELECT id FROM genres WHERE name = '小説'");
+        assert_eq!(query_ids(&conn, "SELECT genre_id FROM books WHERE id = ?1", rashomon), [novel]);
+        let upstairs = id_of(&conn, "SELECT id FROM locations WHERE name = '二階' AND parent_id = 1");
+        let rashomon_copies = query_ids(&conn, "SELECT id FROM copies WHERE book_id = ?1", rashomon);
+        assert_eq!(rashomon_copies.len(), 1);
+        assert_eq!(query_ids(&conn, "SELECT location_id FROM copies WHERE book_id = ?1", rashomon), [upstairs]);
+        assert_eq!(query_ids(&conn, "SELECT location_id FROM copies WHERE book_id = ?1", sanshiro), [1]);
+        assert_eq!(query_ids(&conn, "SELECT copy_id FROM loans WHERE book_id = ?1", rashomon), rashomon_copies);
+        let collected_works = id_of(&conn, "SELECT id FROM series WHERE title = '漱石全集'");
+        assert_eq!(
+            query_ids(&conn, "SELECT series_id FROM book_series WHERE book_id = ?1 AND volume = 3", sanshiro),
+            [collected_works]
+        );
+        assert_eq!(query_ids(&conn, "SELECT tag_id FROM book_tags WHERE book_id = ?1", rashomon), [1]);
+
+        // 追加しなかった本にはタグだけを引き継ぐ
+        let reread = id_of(&conn, "SELECT id FROM tags WHERE name = '再読'");
+        assert_eq!(query_ids(&conn, "SELECT tag_id FROM book_tags WHERE book_id = ?1", 1), [reread]);
+        assert!(query_ids(&conn, "SELECT id FROM copies WHERE book_id = ?1", 1).is_empty());
+        assert_eq!(id_of(&conn, "SELECT COUNT(*) FROM book_history WHERE action = 'insert'"), 2);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::collation;
+use crate::db::{backup_database, backup_files, backups_dir, migrate, schema_version, DbConnection};
+use crate::error::CommandError;
+use crate::models::BackupInfo;
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+
+/// 定期バックアップの間隔。前回から変更が無ければ何もしない。
+const BACKUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// バックアップを新しい順に返す。
+#[tauri::command]
+pub fn list_backups() -> Result<Vec<BackupInfo>, String> {
+    let mut paths = backup_files().map_err(|e| e.to_string())?;
+    paths.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(paths.iter().filter_map(|path| backup_info(path)).collect())
+}
+
+/// 今の状態をすぐにバックアップする。
+#[tauri::command]
+pub fn backup_now(db: State<DbConnection>) -> Result<BackupInfo, String> {
+    let conn = db.0.lock().unwrap();
+    let path = backup_database(&conn)?;
+    backup_info(&path).ok_or_else(|| "バックアップを読み込めませんでした".to_string())
+}
+
+/// バックアップから復元する。スキーマのバージョンを確かめてから、
+/// 今の状態をバックアップに残して差し替え、古いバックアップならマイグレーションを当てる。
+#[tauri::command]
+pub fn restore_backup(file_name: String, db: State<DbConnection>) -> Result<(), CommandError> {
+    if file_name.contains(['/', '\\']) || !is_backup_file_name(&file_name) {
+        return Err(CommandError::Invalid("バックアップのファイル名が不正です".into()));
+    }
+    let path = backups_dir().join(&file_name);
+    if !path.exists() {
+        return Err(CommandError::NotFound(format!(
+            "バックアップ {} が見つかりません",
+            file_name
+        )));
+    }
+
+    // 世代の入れ替えで元のファイルが消えても困らないよう、先にメモリへ読み込んでおく
+    let mut staged = Connection::open_in_memory()?;
+    collation::register(&staged)?;
+    staged
+        .restore(DatabaseName::Main, &path, None::<fn(rusqlite::backup::Progress)>)
+        .map_err(|e| CommandError::Invalid(format!("バックアップを読み込めませんでした: {}", e)))?;
+    validate_backup(&staged)?;
+
+    let mut conn = db.0.lock().unwrap();
+    backup_database(&conn).map_err(CommandError::Database)?;
+    Backup::new(&staged, &mut conn)?.run_to_completion(256, Duration::ZERO, None)?;
+    staged.close().map_err(|(_, e)| e)?;
+    migrate(&mut conn)?;
+    Ok(())
+}
+
+/// 一定間隔で、前回から変更があればバックアップを取るスレッドを起動する。
+pub fn start_backup_schedule(app: AppHandle) {
+    thread::spawn(move || {
+        let mut last_changes = None;
+        loop {
+            thread::sleep(BACKUP_INTERVAL);
+            let db = app.state::<DbConnection>();
+            let conn = db.0.lock().unwrap();
+            let changes: i64 = match conn.query_row("SELECT total_changes()", [], |row| row.get(0)) {
+                Ok(changes) => changes,
+                Err(_) => continue,
+            };
+            // 起動時にバックアップ済みなので、最初の一回は変更があったときだけ取る
+            if last_changes.unwrap_or(0) == changes {
+                continue;
+            }
+            match backup_database(&conn) {
+                Ok(_) => last_changes = Some(changes),
+                Err(e) => eprintln!("定期バックアップに失敗しました: {}", e),
+            }
+        }
+    });
+}
+
+/// 壊れていないか、このアプリのデータベースか、このアプリで読めるバージョンかを確かめる。
+pub(crate) fn validate_backup(conn: &Connection) -> Result<(), CommandError> {
+    let check: String = conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+    if check != "ok" {
+        return Err(CommandError::Invalid(format!("バックアップが壊れています: {}", check)));
+    }
+    let has_tables: bool = conn.query_row(
+        "SELECT COUNT(*) = 2 FROM sqlite_master WHERE type = 'table' AND name IN ('books', 'genres')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_tables {
+        return Err(CommandError::Invalid("蔵書のバックアップではありません".into()));
+    }
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > schema_version() {
+        return Err(CommandError::Invalid(format!(
+            "新しいバージョンのアプリで作られたバックアップです（スキーマ {}、対応は {} まで）",
+            version,
+            schema_version()
+        )));
+    }
+    Ok(())
+}
+
+fn backup_info(path: &Path) -> Option<BackupInfo> {
+    let file_name = path.file_name()?.to_str()?.to_string();
+    if !is_backup_file_name(&file_name) {
+        return None;
+    }
+    let stamp = &file_name["bibly-".len().."bibly-YYYYMMDD-HHMMSS".len()];
+    let created_at = format!(
+        "{}-{}-{} {}:{}:{}",
+        &stamp[0..4],
+        &stamp[4..6],
+        &stamp[6..8],
+        &stamp[9..11],
+        &stamp[11..13],
+        &stamp[13..15]
+    );
+    let size = path.metadata().ok()?.len();
+    let schema_version = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .and_then(|conn| conn.query_row("PRAGMA user_version", [], |row| row.get(0)))
+        .ok();
+    Some(BackupInfo {
+        file_name,
+        created_at,
+        size,
+        schema_version,
+    })
+}
+
+/// `bibly-YYYYMMDD-HHMMSS.sqlite`
+fn is_backup_file_name(name: &str) -> bool {
+    let Some(stamp) = name
+        .strip_prefix("bibly-")
+        .and_then(|rest| rest.strip_suffix(".sqlite"))
+    else {
+        return false;
+    };
+    stamp.len() == 15
+        && stamp
+            .char_indices()
+            .all(|(i, c)| if i == 8 { c == '-' } else { c.is_ascii_digit() })
+}
@@ -1,69 +1,255 @@
-use crate::db::DbConnection;
-use crate::models::{Book, NewBook, UpdateBook};
+use crate::commands::duplicate::find_matches;
+use crate::commands::location::location_kind;
+use crate::commands::reading::normalize_date;
+use crate::commands::series::validate_volume;
+use crate::db::{DbConnection, GENRE_SUBTREE_CTE, SEARCH_BOOK_COLUMNS};
+use crate::error::CommandError;
+use crate::history::{record_change, snapshot_book};
+use crate::models::{
+    Book, BookDateFilter, BookPatch, BookSort, BulkBookPatch, HistoryAction, NewBook, UpdateBook,
+};
+use crate::normalize::{
+    normalize_author, normalize_author_reading, normalize_title_reading, reading_from_kana,
+};
+use rusqlite::{Connection, OptionalExtension};
 use tauri::State;
 
+pub(crate) const BOOK_COLUMNS: &str =
+    "id, isbn, title, author, publisher, price, c_code, is_read, genre_id, author_kana, title_kana, rating,
+     (SELECT COUNT(*) FROM copies WHERE copies.book_id = books.id) AS copy_count, revision,
+     created_at, updated_at";
+
+/// `tag_ids` を指定すると、そのすべてのタグが付いた本だけを返す。
+/// `date_filter` を指定すると、登録日・更新日がその期間にある本だけを返す。
 #[tauri::command]
-pub fn get_all_books(db: State<DbConnection>) -> Result<Vec<Book>, String> {
+pub fn get_all_books(
+    sort: Option<BookSort>,
+    tag_ids: Option<Vec<i64>>,
+    date_filter: Option<BookDateFilter>,
+    db: State<DbConnection>,
+) -> Result<Vec<Book>, String> {
     let conn = db.0.lock().unwrap();
-    let mut stmt = conn
-        .prepare("SELECT id, isbn, title, author, publisher, price, c_code, is_read, genre_id FROM books")
-        .map_err(|e| e.to_string())?;
-    let iter = stmt.query_map([], row_to_book).map_err(|e| e.to_string())?;
-    collect_books(iter)
+    let mut conditions: Vec<String> = tag_condition(tag_ids.as_deref()).into_iter().collect();
+    conditions.extend(date_conditions(&conn, date_filter.as_ref())?);
+    query_books(&conn, &conditions, &[], sort)
 }
 
 #[tauri::command]
-pub fn get_books_by_genre(genre_id: i64, db: State<DbConnection>) -> Result<Vec<Book>, String> {
+pub fn get_books_by_genre(
+    genre_id: i64,
+    sort: Option<BookSort>,
+    tag_ids: Option<Vec<i64>>,
+    include_descendants: Option<bool>,
+    date_filter: Option<BookDateFilter>,
+    db: State<DbConnection>,
+) -> Result<Vec<Book>, String> {
     let conn = db.0.lock().unwrap();
-    let mut stmt = conn
-        .prepare("SELECT id, isbn, title, author, publisher, price, c_code, is_read, genre_id FROM books WHERE genre_id = ?1")
-        .map_err(|e| e.to_string())?;
-    let iter = stmt
-        .query_map([genre_id], row_to_book)
-        .map_err(|e| e.to_string())?;
-    collect_books(iter)
+    let mut conditions = vec![genre_condition(include_descendants.unwrap_or(false))];
+    conditions.extend(tag_condition(tag_ids.as_deref()));
+    conditions.extend(date_conditions(&conn, date_filter.as_ref())?);
+    query_books(&conn, &conditions, &[&genre_id], sort)
+}
+
+/// 書名・著者・出版社・ISBN・読み・レビュー・メモを全文検索する。
+/// 空白で区切った語をすべて含む本を返す。
+#[tauri::command]
+pub fn search_books(
+    query: String,
+    sort: Option<BookSort>,
+    date_filter: Option<BookDateFilter>,
+    db: State<DbConnection>,
+) -> Result<Vec<Book>, String> {
+    // 索引は入力されたままの表記なので、語は NFKC にかけず空白（全角も含む）で区切るだけにする
+    let terms: Vec<String> = query.split_whitespace().map(str::to_string).collect();
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut conditions = Vec::new();
+    let mut params = Vec::new();
+    for (i, term) in terms.iter().enumerate() {
+        let (condition, param) = search_condition(term, i + 1);
+        conditions.push(condition);
+        params.push(param);
+    }
+    let params: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
+    let conn = db.0.lock().unwrap();
+    conditions.extend(date_conditions(&conn, date_filter.as_ref())?);
+    query_books(&conn, &conditions, &params, sort)
+}
+
+/// 1語分の検索条件。trigram は3文字未満の語を MATCH できないので、短い語は LIKE で探す。
+fn search_condition(term: &str, index: usize) -> (String, String) {
+    if term.chars().count() >= 3 {
+        (
+            format!("id IN (SELECT rowid FROM book_search WHERE book_search MATCH ?{})", index),
+            format!("\"{}\"", term.replace('"', "\"\"")),
+        )
+    } else {
+        let likes: Vec<String> = SEARCH_BOOK_COLUMNS
+            .iter()
+            .chain(&["notes"])
+            .map(|column| format!("{} LIKE ?{} ESCAPE '\\'", column, index))
+            .collect();
+        (
+            format!("id IN (SELECT rowid FROM book_search WHERE {})", likes.join(" OR ")),
+            format!(
+                "%{}%",
+                term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+            ),
+        )
+    }
 }
 
+/// 同じ ISBN の本や、著者が同じで書名がほぼ同じ本が既にあれば登録せずに `duplicate` エラーを返す。
+/// UI は候補を見せて、既存の本に1冊追加するか、`allow_duplicate` を付けて登録し直す。
 #[tauri::command]
-pub fn add_book(new_book: NewBook, db: State<DbConnection>) -> Result<Book, String> {
+pub fn add_book(new_book: NewBook, db: State<DbConnection>) -> Result<Book, CommandError> {
     if new_book.title.trim().is_empty() {
-        return Err("タイトル必須".into());
+        return Err(CommandError::Invalid("タイトル必須".into()));
     }
+    validate_volume(new_book.volume).map_err(CommandError::Invalid)?;
     let conn = db.0.lock().unwrap();
+    if !new_book.allow_duplicate.unwrap_or(false) {
+        let matches = find_matches(&conn, &new_book).map_err(CommandError::Database)?;
+        if !matches.is_empty() {
+            return Err(CommandError::Duplicate(matches));
+        }
+    }
+    let id = insert_book(&conn, &new_book).map_err(CommandError::Database)?;
+    get_book(&conn, id).map_err(CommandError::Database)
+}
+
+/// 本を登録し、手元の1冊目・読了記録・シリーズも合わせて作る。登録した本の id を返す。
+pub(crate) fn insert_book(conn: &Connection, new_book: &NewBook) -> Result<i64, String> {
+    if new_book.title.trim().is_empty() {
+        return Err("タイトル必須".into());
+    }
+    validate_volume(new_book.volume)?;
+    let (author, author_kana) = normalize_author_fields(new_book.author.as_deref(), new_book.author_kana.as_deref());
+    let title_kana = normalize_title_kana(&new_book.title, new_book.title_kana.as_deref());
     conn.execute(
-        "INSERT INTO books (title, genre_id, isbn, author, publisher, price, c_code, is_read)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, COALESCE(?8,0))",
+        "INSERT INTO books (title, genre_id, isbn, author, publisher, price, c_code, is_read, author_kana, title_kana)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 0, ?8, ?9)",
         rusqlite::params![
             new_book.title,
             new_book.genre_id,
             new_book.isbn,
-            new_book.author,
+            author,
             new_book.publisher,
             new_book.price,
             new_book.c_code,
-            new_book.is_read
+            author_kana,
+            title_kana
         ],
     )
     .map_err(|e| e.to_string())?;
     let id = conn.last_insert_rowid();
-    {
-        let mut stmt = conn.prepare(
-            "SELECT id, isbn, title, author, publisher, price, c_code, is_read, genre_id FROM books WHERE id = ?1"
-        ).map_err(|e| e.to_string())?;
-        let book = stmt
-            .query_row([id], row_to_book)
+    // 登録した本を手元の1冊目とする
+    conn.execute(
+        "INSERT INTO copies (book_id, acquired_at, location_id) VALUES (?1, date('now', 'localtime'), ?2)",
+        rusqlite::params![id, new_book.location_id],
+    )
+    .map_err(|e| e.to_string())?;
+    // is_read は読書記録から導かれるので、既読なら読了記録を作る
+    if new_book.is_read.unwrap_or(0) != 0 {
+        crate::db::set_read_flag(conn, id, true).map_err(|e| e.to_string())?;
+    }
+    if let Some(series) = new_book.series.as_deref().filter(|s| !s.trim().is_empty()) {
+        let series_id = crate::db::ensure_series(conn, series).map_err(|e| e.to_string())?;
+        crate::db::set_book_series(conn, id, Some(series_id), new_book.volume)
             .map_err(|e| e.to_string())?;
-        Ok(book)
     }
+    let after = snapshot_book(conn, id).map_err(|e| e.to_string())?;
+    record_change(conn, id, HistoryAction::Insert, None, after.as_ref()).map_err(|e| e.to_string())?;
+    Ok(id)
 }
 
+pub(crate) fn get_book(conn: &Connection, id: i64) -> Result<Book, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM books WHERE id = ?1", BOOK_COLUMNS),
+        [id],
+        row_to_book,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Book with id {} not found", id))
+}
+
+/// `book.revision` を渡すと、読み込んだ後に他で更新された本は保存せずに `conflict` エラーを返す。
 #[tauri::command]
-pub fn update_book(book: UpdateBook, db: State<DbConnection>) -> Result<Book, String> {
+pub fn update_book(book: UpdateBook, db: State<DbConnection>) -> Result<Book, CommandError> {
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction()?;
+    if let Some(revision) = book.revision {
+        check_revision(&tx, book.id, revision)?;
+    }
+    write_book(&tx, &book)?;
+    tx.commit()?;
+    get_book(&conn, book.id).map_err(CommandError::Database)
+}
+
+/// 本の一部の項目だけを変えて、更新後の本を返す。`patch.revision` が今の版番号と違えば
+/// （読み込んだ後に他で更新されていれば）何も変えずに `conflict` エラーを返す。
+#[tauri::command]
+pub fn patch_book(patch: BookPatch, db: State<DbConnection>) -> Result<Book, CommandError> {
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction()?;
+    let current = check_revision(&tx, patch.id, patch.revision)?;
+    let book = UpdateBook {
+        id: current.id,
+        isbn: patch.isbn.unwrap_or(current.isbn),
+        title: patch.title.unwrap_or(current.title),
+        title_kana: patch.title_kana.unwrap_or(current.title_kana),
+        author: patch.author.unwrap_or(current.author),
+        author_kana: patch.author_kana.unwrap_or(current.author_kana),
+        publisher: patch.publisher.unwrap_or(current.publisher),
+        price: patch.price.unwrap_or(current.price),
+        c_code: patch.c_code.unwrap_or(current.c_code),
+        is_read: patch.is_read.map_or(current.is_read, i64::from),
+        genre_id: patch.genre_id.unwrap_or(current.genre_id),
+        revision: None,
+    };
     if book.title.trim().is_empty() {
-        return Err("タイトル必須".into());
+        return Err(CommandError::Invalid("タイトル必須".into()));
     }
-    let conn = db.0.lock().unwrap();
-    conn.execute(
+    write_book(&tx, &book)?;
+    tx.commit()?;
+    get_book(&conn, book.id).map_err(CommandError::Database)
+}
+
+/// ゴミ箱に無い本を返す。版番号が `revision` と違えば `conflict` エラーにする。
+pub(crate) fn check_revision(conn: &Connection, id: i64, revision: i64) -> Result<Book, CommandError> {
+    let book = conn
+        .query_row(
+            &format!("SELECT {} FROM books WHERE id = ?1 AND deleted_at IS NULL", BOOK_COLUMNS),
+            [id],
+            row_to_book,
+        )
+        .optional()?
+        .ok_or_else(|| CommandError::NotFound(format!("Book with id {} not found", id)))?;
+    if book.revision != revision {
+        return Err(CommandError::Conflict(format!(
+            "「{}」は読み込んだ後に変更されています。読み込み直してからもう一度保存してください",
+            book.title
+        )));
+    }
+    Ok(book)
+}
+
+const DATED_READS_MESSAGE: &str = "読了日のある読書記録があるため未読に戻せません。読書記録から削除してください";
+
+/// `book` の内容で本を上書きし、変更を履歴に残す。
+pub(crate) fn write_book(conn: &Connection, book: &UpdateBook) -> Result<(), CommandError> {
+    if book.title.trim().is_empty() {
+        return Err(CommandError::Invalid("タイトル必須".into()));
+    }
+    let (author, author_kana) = normalize_author_fields(book.author.as_deref(), book.author_kana.as_deref());
+    let title_kana = normalize_title_kana(&book.title, book.title_kana.as_deref());
+    if book.is_read == 0 && crate::db::has_dated_reads(conn, book.id)? {
+        return Err(CommandError::Conflict(DATED_READS_MESSAGE.into()));
+    }
+    let before = snapshot_book(conn, book.id)?;
+    let affected = conn.execute(
         "UPDATE books SET
             isbn = ?1,
             title = ?2,
@@ -71,48 +257,177 @@ pub fn update_book(book: UpdateBook, db: State<DbConnection>) -> Result<Book, St
             publisher = ?4,
             price = ?5,
             c_code = ?6,
-            is_read = ?7,
-            genre_id = ?8
-         WHERE id = ?9",
+            genre_id = ?7,
+            author_kana = ?8,
+            title_kana = ?9
+         WHERE id = ?10 AND deleted_at IS NULL",
         rusqlite::params![
             book.isbn,
             book.title,
-            book.author,
+            author,
             book.publisher,
             book.price,
             book.c_code,
-            book.is_read,
             book.genre_id,
+            author_kana,
+            title_kana,
             book.id
         ],
-    )
-    .map_err(|e| e.to_string())?;
-    {
-        let mut stmt = conn.prepare(
-            "SELECT id, isbn, title, author, publisher, price, c_code, is_read, genre_id FROM books WHERE id = ?1"
-        ).map_err(|e| e.to_string())?;
-        let updated = stmt
-            .query_row([book.id], row_to_book)
-            .map_err(|e| e.to_string())?;
-        Ok(updated)
+    )?;
+    if affected == 0 {
+        return Err(CommandError::NotFound(format!("Book with id {} not found", book.id)));
     }
+    crate::db::set_read_flag(conn, book.id, book.is_read != 0)?;
+    let after = snapshot_book(conn, book.id)?;
+    record_change(conn, book.id, HistoryAction::Update, before.as_ref(), after.as_ref())?;
+    Ok(())
 }
 
+/// 本をゴミ箱に移す。ゴミ箱の本は一覧や集計に出なくなり、
+/// `restore_from_trash` で戻せる。書影と関連する記録は完全に削除するまで残す。
 #[tauri::command]
 pub fn delete_book(id: i64, db: State<DbConnection>) -> Result<(), String> {
-    let conn = db.0.lock().unwrap();
-    match crate::db::delete_book(&conn, id) {
-        Ok(affected) if affected > 0 => Ok(()),
-        Ok(_) => Err(format!("Book with id {} not found", id)),
-        Err(e) => Err(e.to_string()),
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    if !trash_book(&tx, id).map_err(|e| e.to_string())? {
+        return Err(format!("Book with id {} not found", id));
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// 本をゴミ箱に移して削除の履歴を残す。ゴミ箱に無い本が見つからなければ false。
+pub(crate) fn trash_book(conn: &Connection, id: i64) -> Result<bool, rusqlite::Error> {
+    let before = snapshot_book(conn, id)?;
+    let affected = conn.execute(
+        "UPDATE books SET deleted_at = datetime('now', 'localtime')
+         WHERE id = ?1 AND deleted_at IS NULL",
+        [id],
+    )?;
+    if affected == 0 {
+        return Ok(false);
+    }
+    let after = snapshot_book(conn, id)?;
+    record_change(conn, id, HistoryAction::Delete, before.as_ref(), after.as_ref())?;
+    Ok(true)
+}
+
+/// 選択した本に `patch` をまとめて適用し、更新後の本を返す。
+/// 見つからない本（ゴミ箱の本を含む）が1冊でもあれば何も変えない。
+#[tauri::command]
+pub fn bulk_update_books(
+    book_ids: Vec<i64>,
+    patch: BulkBookPatch,
+    db: State<DbConnection>,
+) -> Result<Vec<Book>, CommandError> {
+    let mut conn = db.0.lock().unwrap();
+    if let Some(Some(genre_id)) = patch.genre_id {
+        ensure_exists(&conn, "genres", genre_id, "Genre")?;
+    }
+    if let Some(Some(location_id)) = patch.location_id {
+        location_kind(&conn, location_id)?;
+    }
+    for &tag_id in patch.add_tag_ids.iter().chain(&patch.remove_tag_ids) {
+        ensure_exists(&conn, "tags", tag_id, "Tag")?;
+    }
+    let publisher = patch
+        .publisher
+        .as_ref()
+        .map(|publisher| publisher.as_deref().map(str::trim).filter(|p| !p.is_empty()));
+
+    let tx = conn.transaction()?;
+    for &book_id in &book_ids {
+        let active: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM books WHERE id = ?1 AND deleted_at IS NULL)",
+            [book_id],
+            |row| row.get(0),
+        )?;
+        if !active {
+            return Err(CommandError::NotFound(format!("Book with id {} not found", book_id)));
+        }
+        let before = snapshot_book(&tx, book_id)?;
+        if let Some(genre_id) = patch.genre_id {
+            tx.execute(
+                "UPDATE books SET genre_id = ?1 WHERE id = ?2",
+                rusqlite::params![genre_id, book_id],
+            )?;
+        }
+        if let Some(publisher) = publisher {
+            tx.execute(
+                "UPDATE books SET publisher = ?1 WHERE id = ?2",
+                rusqlite::params![publisher, book_id],
+            )?;
+        }
+        if let Some(is_read) = patch.is_read {
+            if !is_read && crate::db::has_dated_reads(&tx, book_id)? {
+                return Err(CommandError::Conflict(DATED_READS_MESSAGE.into()));
+            }
+            crate::db::set_read_flag(&tx, book_id, is_read)?;
+        }
+        for &tag_id in &patch.add_tag_ids {
+            tx.execute(
+                "INSERT OR IGNORE INTO book_tags (book_id, tag_id) VALUES (?1, ?2)",
+                [book_id, tag_id],
+            )?;
+        }
+        for &tag_id in &patch.remove_tag_ids {
+            tx.execute(
+                "DELETE FROM book_tags WHERE book_id = ?1 AND tag_id = ?2",
+                [book_id, tag_id],
+            )?;
+        }
+        if let Some(location_id) = patch.location_id {
+            tx.execute(
+                "UPDATE copies SET location_id = ?1 WHERE book_id = ?2",
+                rusqlite::params![location_id, book_id],
+            )?;
+        }
+        let after = snapshot_book(&tx, book_id)?;
+        if before != after {
+            record_change(&tx, book_id, HistoryAction::Update, before.as_ref(), after.as_ref())?;
+        }
+    }
+    tx.commit()?;
+    book_ids
+        .iter()
+        .map(|&id| get_book(&conn, id).map_err(CommandError::Database))
+        .collect()
+}
+
+fn ensure_exists(conn: &Connection, table: &str, id: i64, label: &str) -> Result<(), CommandError> {
+    let exists: bool = conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM {} WHERE id = ?1)", table),
+        [id],
+        |row| row.get(0),
+    )?;
+    if exists {
+        Ok(())
+    } else {
+        Err(CommandError::NotFound(format!("{} with id {} not found", label, id)))
     }
 }
 
+/// ジャンルにある本の冊数（同じ本を複数持っていればその分も数える）。
+/// 空のジャンルを片付けるときは `include_trashed` を付けて、ゴミ箱の本も数える
+/// （ゴミ箱から戻した本がジャンルを失わないように）。
 #[tauri::command]
-pub fn get_book_count_by_genre(genre_id: i64, db: State<DbConnection>) -> Result<i64, String> {
+pub fn get_book_count_by_genre(
+    genre_id: i64,
+    include_descendants: Option<bool>,
+    include_trashed: Option<bool>,
+    db: State<DbConnection>,
+) -> Result<i64, String> {
     let conn = db.0.lock().unwrap();
     let mut stmt = conn
-        .prepare("SELECT COUNT(*) FROM books WHERE genre_id = ?1")
+        .prepare(&format!(
+            "SELECT COUNT(*) FROM copies JOIN books ON books.id = copies.book_id
+             WHERE {}{}",
+            if include_trashed.unwrap_or(false) {
+                ""
+            } else {
+                "books.deleted_at IS NULL AND "
+            },
+            genre_condition(include_descendants.unwrap_or(false))
+        ))
         .map_err(|e| e.to_string())?;
     let count: i64 = stmt
         .query_row([genre_id], |row| row.get(0))
@@ -120,20 +435,128 @@ pub fn get_book_count_by_genre(genre_id: i64, db: State<DbConnection>) -> Result
     Ok(count)
 }
 
-fn row_to_book(row: &rusqlite::Row) -> rusqlite::Result<Book> {
+/// `?1` のジャンルに属する書籍を絞り込む条件。子孫ジャンルを含める場合は再帰 CTE を使う。
+fn genre_condition(include_descendants: bool) -> String {
+    if include_descendants {
+        format!("genre_id IN ({} SELECT id FROM subtree)", GENRE_SUBTREE_CTE)
+    } else {
+        "genre_id = ?1".to_string()
+    }
+}
+
+pub(crate) fn row_to_book(row: &rusqlite::Row) -> rusqlite::Result<Book> {
     Ok(Book {
         id: row.get(0)?,
         isbn: row.get(1)?,
         title: row.get(2)?,
+        title_kana: row.get(10)?,
         author: row.get(3)?,
         publisher: row.get(4)?,
         price: row.get(5)?,
         c_code: row.get(6)?,
         is_read: row.get(7)?,
         genre_id: row.get(8)?,
+        author_kana: row.get(9)?,
+        rating: row.get(11)?,
+        copy_count: row.get(12)?,
+        revision: row.get(13)?,
+        created_at: row.get(14)?,
+        updated_at: row.get(15)?,
     })
 }
 
+/// 一覧取得の共通部分。`conditions` は AND で連結して WHERE 句にする。
+/// ゴミ箱にある本は含めない。
+pub(crate) fn query_books(
+    conn: &rusqlite::Connection,
+    conditions: &[String],
+    params: &[&dyn rusqlite::ToSql],
+    sort: Option<BookSort>,
+) -> Result<Vec<Book>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM books WHERE {} ORDER BY {}",
+            BOOK_COLUMNS,
+            std::iter::once("deleted_at IS NULL")
+                .chain(conditions.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(" AND "),
+            order_by(sort)
+        ))
+        .map_err(|e| e.to_string())?;
+    let iter = stmt.query_map(params, row_to_book).map_err(|e| e.to_string())?;
+    collect_books(iter)
+}
+
+/// 指定したタグがすべて付いている本に絞り込む条件。id は整数なので SQL に直接埋め込む。
+fn tag_condition(tag_ids: Option<&[i64]>) -> Option<String> {
+    let tag_ids = tag_ids.filter(|ids| !ids.is_empty())?;
+    let mut unique = tag_ids.to_vec();
+    unique.sort_unstable();
+    unique.dedup();
+    let list = unique.iter().map(i64::to_string).collect::<Vec<_>>().join(", ");
+    Some(format!(
+        "id IN (SELECT book_id FROM book_tags WHERE tag_id IN ({}) \
+         GROUP BY book_id HAVING COUNT(DISTINCT tag_id) = {})",
+        list,
+        unique.len()
+    ))
+}
+
+/// 登録日・更新日で絞り込む条件。日付は `normalize_date` で "YYYY-MM-DD" に揃えてから埋め込む。
+fn date_conditions(conn: &Connection, filter: Option<&BookDateFilter>) -> Result<Vec<String>, String> {
+    let Some(filter) = filter else {
+        return Ok(Vec::new());
+    };
+    let column = filter.field.column();
+    let mut conditions = Vec::new();
+    if let Some(from) = filter.from.as_deref() {
+        let from = normalize_date(conn, Some(from))?;
+        conditions.push(format!("{} >= '{}'", column, from));
+    }
+    if let Some(to) = filter.to.as_deref() {
+        let to = normalize_date(conn, Some(to))?;
+        conditions.push(format!("{} < date('{}', '+1 day')", column, to));
+    }
+    Ok(conditions)
+}
+
+/// 一覧の ORDER BY 句。読みが無い本は表記で比較し、著者不明の本は最後に回す。
+fn order_by(sort: Option<BookSort>) -> &'static str {
+    match sort.unwrap_or_default() {
+        BookSort::Title => "COALESCE(title_kana, title) COLLATE KANA, id",
+        BookSort::Author => {
+            "author IS NULL, COALESCE(author_kana, author) COLLATE KANA, \
+             COALESCE(title_kana, title) COLLATE KANA, id"
+        }
+        BookSort::CreatedAt => "created_at DESC, id DESC",
+        BookSort::UpdatedAt => "updated_at DESC, id DESC",
+    }
+}
+
+/// 書名の読みを正規化する。読みが無く書名がかなだけなら、それを読みとして使う。
+pub(crate) fn normalize_title_kana(title: &str, title_kana: Option<&str>) -> Option<String> {
+    title_kana
+        .map(normalize_title_reading)
+        .filter(|k| !k.is_empty())
+        .or_else(|| reading_from_kana(title))
+}
+
+/// 著者名と読みを正規化する。読みが無く著者名がかなだけなら、それを読みとして使う。
+pub(crate) fn normalize_author_fields(
+    author: Option<&str>,
+    author_kana: Option<&str>,
+) -> (Option<String>, Option<String>) {
+    let author = author
+        .map(normalize_author)
+        .filter(|a| !a.is_empty());
+    let author_kana = author_kana
+        .map(normalize_author_reading)
+        .filter(|k| !k.is_empty())
+        .or_else(|| author.as_deref().and_then(reading_from_kana));
+    (author, author_kana)
+}
+
 fn collect_books<I>(iter: I) -> Result<Vec<Book>, String>
 where
     I: Iterator<Item = Result<Book, rusqlite::Error>>,
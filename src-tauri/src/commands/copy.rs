@@ -0,0 +1,240 @@
+use crate::commands::location::location_kind;
+use crate::commands::reading::normalize_date;
+use crate::db::DbConnection;
+use crate::models::{AcquisitionKind, Copy, NewCopy, Purchase, UpdateCopy};
+use rusqlite::{Connection, OptionalExtension};
+use tauri::State;
+
+pub(crate) const COPY_COLUMNS: &str =
+    "id, book_id, condition, acquired_at, purchase_price, store, acquisition, location_id, notes";
+
+/// 本の手元の冊を、入手した順で返す。
+#[tauri::command]
+pub fn get_book_copies(book_id: i64, db: State<DbConnection>) -> Result<Vec<Copy>, String> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM copies WHERE book_id = ?1 ORDER BY acquired_at IS NULL, acquired_at, id",
+            COPY_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([book_id], row_to_copy)
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// 同じ本をもう1冊登録する。
+#[tauri::command]
+pub fn add_copy(copy: NewCopy, db: State<DbConnection>) -> Result<Copy, String> {
+    let conn = db.0.lock().unwrap();
+    let book_exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM books WHERE id = ?1 AND deleted_at IS NULL)",
+            [copy.book_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !book_exists {
+        return Err(format!("Book with id {} not found", copy.book_id));
+    }
+    validate_copy(&conn, copy.purchase_price, copy.acquisition, copy.location_id)?;
+    let acquired_at = normalize_date(&conn, copy.acquired_at.as_deref())?;
+    conn.execute(
+        "INSERT INTO copies
+            (book_id, condition, acquired_at, purchase_price, store, acquisition, location_id, notes)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![
+            copy.book_id,
+            non_empty(copy.condition),
+            acquired_at,
+            copy.purchase_price,
+            non_empty(copy.store),
+            copy.acquisition.map(AcquisitionKind::as_str),
+            copy.location_id,
+            non_empty(copy.notes)
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    get_copy(&conn, conn.last_insert_rowid())
+}
+
+#[tauri::command]
+pub fn update_copy(copy: UpdateCopy, db: State<DbConnection>) -> Result<Copy, String> {
+    let conn = db.0.lock().unwrap();
+    validate_copy(&conn, copy.purchase_price, copy.acquisition, copy.location_id)?;
+    let acquired_at = copy
+        .acquired_at
+        .filter(|date| !date.trim().is_empty())
+        .map(|date| normalize_date(&conn, Some(&date)))
+        .transpose()?;
+    let affected = conn
+        .execute(
+            "UPDATE copies SET
+                condition = ?1,
+                acquired_at = ?2,
+                purchase_price = ?3,
+                store = ?4,
+                acquisition = ?5,
+                location_id = ?6,
+                notes = ?7
+             WHERE id = ?8",
+            rusqlite::params![
+                non_empty(copy.condition),
+                acquired_at,
+                copy.purchase_price,
+                non_empty(copy.store),
+                copy.acquisition.map(AcquisitionKind::as_str),
+                copy.location_id,
+                non_empty(copy.notes),
+                copy.id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    if affected == 0 {
+        return Err(format!("Copy with id {} not found", copy.id));
+    }
+    get_copy(&conn, copy.id)
+}
+
+/// 購入価格・書店・入手日・入手の種類を記録する。`acquired_at` を省略すると今日の日付になる。
+#[tauri::command]
+pub fn record_purchase(purchase: Purchase, db: State<DbConnection>) -> Result<Copy, String> {
+    let conn = db.0.lock().unwrap();
+    validate_copy(&conn, purchase.purchase_price, Some(purchase.acquisition), None)?;
+    let acquired_at = normalize_date(&conn, purchase.acquired_at.as_deref())?;
+    let affected = conn
+        .execute(
+            "UPDATE copies SET acquired_at = ?1, purchase_price = ?2, store = ?3, acquisition = ?4
+             WHERE id = ?5",
+            rusqlite::params![
+                acquired_at,
+                purchase.purchase_price,
+                non_empty(purchase.store),
+                purchase.acquisition.as_str(),
+                purchase.copy_id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    if affected == 0 {
+        return Err(format!("Copy with id {} not found", purchase.copy_id));
+    }
+    get_copy(&conn, purchase.copy_id)
+}
+
+/// 1冊を手放す。最後の1冊は本ごと削除するので、ここでは削除しない。
+#[tauri::command]
+pub fn delete_copy(id: i64, db: State<DbConnection>) -> Result<(), String> {
+    let mut conn = db.0.lock().unwrap();
+    let copy = get_copy(&conn, id)?;
+    let copy_count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM copies WHERE book_id = ?1",
+            [copy.book_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if copy_count <= 1 {
+        return Err("最後の1冊は削除できません。本ごと削除してください".into());
+    }
+    let on_loan: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM loans WHERE copy_id = ?1 AND returned_at IS NULL)",
+            [id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if on_loan {
+        return Err("貸出中の本は削除できません".into());
+    }
+    crate::db::delete_copy(&mut conn, id).map_err(|e| e.to_string())
+}
+
+/// 選択した冊をまとめて移動する。`location_id` が None なら置き場所なしにする。
+#[tauri::command]
+pub fn move_copies_to_location(
+    copy_ids: Vec<i64>,
+    location_id: Option<i64>,
+    db: State<DbConnection>,
+) -> Result<(), String> {
+    let mut conn = db.0.lock().unwrap();
+    if let Some(location_id) = location_id {
+        location_kind(&conn, location_id).map_err(|e| e.to_string())?;
+    }
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for copy_id in &copy_ids {
+        let affected = tx
+            .execute(
+                "UPDATE copies SET location_id = ?1 WHERE id = ?2",
+                rusqlite::params![location_id, copy_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if affected == 0 {
+            return Err(format!("Copy with id {} not found", copy_id));
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+pub(crate) fn get_copy(conn: &Connection, id: i64) -> Result<Copy, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM copies WHERE id = ?1", COPY_COLUMNS),
+        [id],
+        row_to_copy,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Copy with id {} not found", id))
+}
+
+pub(crate) fn validate_copy(
+    conn: &Connection,
+    purchase_price: Option<i64>,
+    acquisition: Option<AcquisitionKind>,
+    location_id: Option<i64>,
+) -> Result<(), String> {
+    if purchase_price.is_some_and(|price| price < 0) {
+        return Err("購入価格が不正です".into());
+    }
+    if acquisition == Some(AcquisitionKind::Gift) && purchase_price.is_some_and(|price| price > 0) {
+        return Err("贈られた本に購入価格は指定できません".into());
+    }
+    if let Some(location_id) = location_id {
+        location_kind(conn, location_id).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn non_empty(text: Option<String>) -> Option<String> {
+    text.map(|t| t.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+pub(crate) fn row_to_copy(row: &rusqlite::Row) -> rusqlite::Result<Copy> {
+    Ok(Copy {
+        id: row.get(0)?,
+        book_id: row.get(1)?,
+        condition: row.get(2)?,
+        acquired_at: row.get(3)?,
+        purchase_price: row.get(4)?,
+        store: row.get(5)?,
+        acquisition: acquisition_kind(row, 6)?,
+        location_id: row.get(7)?,
+        notes: row.get(8)?,
+    })
+}
+
+pub(crate) fn acquisition_kind(
+    row: &rusqlite::Row,
+    idx: usize,
+) -> rusqlite::Result<Option<AcquisitionKind>> {
+    let Some(kind) = row.get::<_, Option<String>>(idx)? else {
+        return Ok(None);
+    };
+    AcquisitionKind::parse(&kind).map(Some).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            idx,
+            rusqlite::types::Type::Text,
+            format!("unknown acquisition kind: {}", kind).into(),
+        )
+    })
+}
@@ -0,0 +1,183 @@
+use crate::commands::ndl_api::ndl_thumbnail_url;
+use crate::db::{data_dir, DbConnection};
+use crate::normalize::normalize_isbn;
+use image::ImageFormat;
+use rusqlite::{Connection, OptionalExtension};
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tauri::http::{header::CONTENT_TYPE, Request, Response, StatusCode};
+use tauri::State;
+
+/// `cover://localhost/{book_id}/thumb` のように書影を返す URI スキーム
+pub const COVER_SCHEME: &str = "cover";
+
+/// 一覧に出す縮小画像は、縦横比を保ってこの枠に収める
+const THUMBNAIL_WIDTH: u32 = 160;
+const THUMBNAIL_HEIGHT: u32 = 240;
+const THUMBNAIL_FILE: &str = "thumb.jpg";
+
+/// 書影をダウンロードして、元画像と縮小画像を保存する。
+/// `url` は fetch_book_info_* が返す `cover_url`。省略すると ISBN から NDL サーチの書影を探す。
+#[tauri::command]
+pub async fn download_book_cover(
+    book_id: i64,
+    url: Option<String>,
+    db: State<'_, DbConnection>,
+) -> Result<(), String> {
+    let isbn = {
+        let conn = db.0.lock().unwrap();
+        book_isbn(&conn, book_id)?
+    };
+    let url = match url.filter(|url| !url.trim().is_empty()) {
+        Some(url) => url,
+        None => {
+            let isbn13 = isbn
+                .as_deref()
+                .and_then(normalize_isbn)
+                .ok_or("ISBNが無いため書影を探せません")?;
+            ndl_thumbnail_url(&isbn13)
+        }
+    };
+
+    let resp = reqwest::get(&url).await.map_err(|e| e.to_string())?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err("書影が見つかりませんでした".to_string());
+    }
+    if !resp.status().is_success() {
+        return Err(format!("書影の取得に失敗しました (HTTP {})", resp.status()));
+    }
+    let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
+    save_cover(book_id, &bytes)
+}
+
+/// 手元の画像ファイルを書影にする。
+#[tauri::command]
+pub fn set_book_cover_from_file(
+    book_id: i64,
+    path: String,
+    db: State<DbConnection>,
+) -> Result<(), String> {
+    {
+        let conn = db.0.lock().unwrap();
+        book_isbn(&conn, book_id)?;
+    }
+    let bytes = fs::read(&path).map_err(|e| format!("画像を読み込めませんでした: {}", e))?;
+    save_cover(book_id, &bytes)
+}
+
+#[tauri::command]
+pub fn delete_book_cover(book_id: i64) -> Result<(), String> {
+    remove_cover(book_id).map_err(|e| e.to_string())
+}
+
+/// 書影のある本の id
+#[tauri::command]
+pub fn get_cover_book_ids() -> Result<Vec<i64>, String> {
+    let dir = covers_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut ids: Vec<i64> = fs::read_dir(&dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join(THUMBNAIL_FILE).exists())
+        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+        .collect();
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+/// `cover://localhost/{book_id}/thumb`（縮小画像）と `/{book_id}/original`（元画像）を返す。
+pub fn cover_protocol(request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let mut parts = request.uri().path().trim_matches('/').split('/');
+    let book_id = parts.next().and_then(|id| id.parse::<i64>().ok());
+    let path = match (book_id, parts.next().unwrap_or("thumb")) {
+        (Some(book_id), "thumb") => Some(cover_dir(book_id).join(THUMBNAIL_FILE)),
+        (Some(book_id), "original") => original_path(book_id),
+        _ => None,
+    };
+    match path.and_then(|path| Some((fs::read(&path).ok()?, path))) {
+        Some((bytes, path)) => {
+            let mime = ImageFormat::from_path(&path)
+                .map(|format| format.to_mime_type())
+                .unwrap_or("application/octet-stream");
+            Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, mime)
+                .body(bytes)
+                .expect("cover response")
+        }
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Vec::new())
+            .expect("cover response"),
+    }
+}
+
+/// 本を削除したときなどに、その本の書影を消す。
+pub(crate) fn remove_cover(book_id: i64) -> std::io::Result<()> {
+    let dir = cover_dir(book_id);
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+/// 統合した本の書影を引き継ぐ。統合先に書影があればそちらを残す。
+pub(crate) fn move_cover(from_book_id: i64, to_book_id: i64) -> std::io::Result<()> {
+    let (from, to) = (cover_dir(from_book_id), cover_dir(to_book_id));
+    if from.exists() && !to.exists() {
+        fs::rename(from, to)
+    } else {
+        remove_cover(from_book_id)
+    }
+}
+
+/// 画像として読めることを確かめてから、元画像と JPEG の縮小画像を書き出す。
+fn save_cover(book_id: i64, bytes: &[u8]) -> Result<(), String> {
+    let format = image::guess_format(bytes).map_err(|_| "画像の形式に対応していません".to_string())?;
+    let image = image::load_from_memory_with_format(bytes, format)
+        .map_err(|e| format!("画像を読み込めませんでした: {}", e))?;
+    let mut thumbnail = Vec::new();
+    image
+        .thumbnail(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT)
+        .to_rgb8()
+        .write_to(&mut Cursor::new(&mut thumbnail), ImageFormat::Jpeg)
+        .map_err(|e| e.to_string())?;
+
+    // 形式が変わると拡張子も変わるので、前の書影はまとめて消す
+    remove_cover(book_id).map_err(|e| e.to_string())?;
+    let dir = cover_dir(book_id);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let extension = format.extensions_str().first().copied().unwrap_or("img");
+    fs::write(dir.join(format!("original.{}", extension)), bytes).map_err(|e| e.to_string())?;
+    fs::write(dir.join(THUMBNAIL_FILE), thumbnail).map_err(|e| e.to_string())
+}
+
+fn book_isbn(conn: &Connection, book_id: i64) -> Result<Option<String>, String> {
+    conn.query_row("SELECT isbn FROM books WHERE id = ?1", [book_id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Book with id {} not found", book_id))
+}
+
+pub(crate) fn covers_dir() -> PathBuf {
+    data_dir().join("covers")
+}
+
+pub(crate) fn cover_dir(book_id: i64) -> PathBuf {
+    covers_dir().join(book_id.to_string())
+}
+
+fn original_path(book_id: i64) -> Option<PathBuf> {
+    fs::read_dir(cover_dir(book_id))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| is_original(path))
+}
+
+fn is_original(path: &Path) -> bool {
+    path.file_stem().and_then(|stem| stem.to_str()) == Some("original")
+}
@@ -0,0 +1,299 @@
+use crate::commands::book::{get_book, normalize_author_fields, query_books};
+use crate::commands::cover::move_cover;
+use crate::db::DbConnection;
+use crate::error::CommandError;
+use crate::models::{Book, DuplicateGroup, DuplicateMatch, DuplicateReason, MergeBooks, NewBook};
+use crate::normalize::{
+    isbn10_from_isbn13, normalize_author, normalize_isbn, split_volume, unify_width,
+};
+use rusqlite::Connection;
+use std::collections::HashMap;
+use tauri::State;
+
+/// 書名の類似度（1 - 編集距離 / 長い方の文字数）がこれ以上なら同じ本とみなす
+const TITLE_SIMILARITY_THRESHOLD: f64 = 0.85;
+
+/// 蔵書全体から重複していそうな本をまとめて返す。ISBN が同じもの、
+/// 続いて著者が同じで書名がほぼ同じもの（どちらかに ISBN が無い場合だけ）の順に並ぶ。
+#[tauri::command]
+pub fn find_duplicates(db: State<DbConnection>) -> Result<Vec<DuplicateGroup>, String> {
+    let conn = db.0.lock().unwrap();
+    let books = query_books(&conn, &[], &[], None)?;
+    let keys: Vec<MatchKey> = books
+        .iter()
+        .map(|b| MatchKey::new(b.isbn.as_deref(), &b.title, b.author.as_deref()))
+        .collect();
+
+    let mut isbn_groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of_isbn: HashMap<&str, usize> = HashMap::new();
+    for (i, key) in keys.iter().enumerate() {
+        if let Some(isbn) = key.isbn.as_deref() {
+            let group = *group_of_isbn.entry(isbn).or_insert_with(|| {
+                isbn_groups.push(Vec::new());
+                isbn_groups.len() - 1
+            });
+            isbn_groups[group].push(i);
+        }
+    }
+
+    // 著者ごとに分けてから書名を比べ、似ているものどうしをつなげる
+    let mut by_author: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, key) in keys.iter().enumerate() {
+        by_author.entry(key.author.as_str()).or_default().push(i);
+    }
+    let mut parent: Vec<usize> = (0..books.len()).collect();
+    for members in by_author.values() {
+        for (n, &i) in members.iter().enumerate() {
+            for &j in &members[n + 1..] {
+                if keys[i].isbn.is_some() && keys[i].isbn == keys[j].isbn {
+                    continue;
+                }
+                if keys[i].is_similar(&keys[j]) {
+                    let (a, b) = (find_root(&mut parent, i), find_root(&mut parent, j));
+                    parent[a.max(b)] = a.min(b);
+                }
+            }
+        }
+    }
+    let mut similar_groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of_root: HashMap<usize, usize> = HashMap::new();
+    for i in 0..books.len() {
+        let root = find_root(&mut parent, i);
+        let group = *group_of_root.entry(root).or_insert_with(|| {
+            similar_groups.push(Vec::new());
+            similar_groups.len() - 1
+        });
+        similar_groups[group].push(i);
+    }
+
+    let take = |indices: &[usize]| -> Vec<Book> { indices.iter().map(|&i| books[i].clone()).collect() };
+    let mut groups = Vec::new();
+    for indices in isbn_groups.iter().filter(|g| g.len() > 1) {
+        groups.push(DuplicateGroup {
+            reason: DuplicateReason::Isbn,
+            books: take(indices),
+        });
+    }
+    for indices in similar_groups.iter().filter(|g| g.len() > 1) {
+        groups.push(DuplicateGroup {
+            reason: DuplicateReason::TitleAuthor,
+            books: take(indices),
+        });
+    }
+    Ok(groups)
+}
+
+/// `source_id` の本を `target_id` の本に統合し、統合後の本を返す。
+/// 手元の冊・貸出・読書記録・メモ・タグは target に引き継ぐ。
+#[tauri::command]
+pub fn merge_books(merge: MergeBooks, db: State<DbConnection>) -> Result<Book, CommandError> {
+    if merge.target_id == merge.source_id {
+        return Err(CommandError::Invalid("同じ本どうしは統合できません".into()));
+    }
+    let mut conn = db.0.lock().unwrap();
+    for id in [merge.target_id, merge.source_id] {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM books WHERE id = ?1 AND deleted_at IS NULL)",
+            [id],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Err(CommandError::NotFound(format!("Book with id {} not found", id)));
+        }
+    }
+    crate::db::merge_books(&mut conn, merge.target_id, merge.source_id, &merge.take_from_source)?;
+    let _ = move_cover(merge.source_id, merge.target_id);
+    get_book(&conn, merge.target_id).map_err(CommandError::Database)
+}
+
+/// 登録しようとしている本と重複していそうな既存の本。ISBN が一致するものを先に返す。
+/// 書名は著者が同じ本とだけ比べるので、候補は SQL で絞ってから読み込む。
+pub(crate) fn find_matches(conn: &Connection, new_book: &NewBook) -> Result<Vec<DuplicateMatch>, String> {
+    let key = MatchKey::new(new_book.isbn.as_deref(), &new_book.title, new_book.author.as_deref());
+    let mut matched: Vec<(i64, DuplicateReason)> = Vec::new();
+    if let Some(isbn13) = key.isbn.as_deref() {
+        // 索引を使えるよう、保存されていそうな表記（入力そのまま・13桁・10桁）で引く。
+        // ハイフンや空白の入った表記は取り除いてから比べる
+        let raw = new_book.isbn.as_deref().unwrap_or_default().trim();
+        let isbn10 = isbn10_from_isbn13(isbn13);
+        let mut stmt = conn
+            .prepare(
+                "SELECT id FROM books
+                 WHERE deleted_at IS NULL
+                   AND (isbn IN (?1, ?2, ?3)
+                        OR REPLACE(REPLACE(isbn, '-', ''), ' ', '') IN (?2, ?3))",
+            )
+            .map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map(rusqlite::params![raw, isbn13, isbn10], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        matched.extend(ids.into_iter().map(|id| (id, DuplicateReason::Isbn)));
+    }
+
+    // 著者は登録時と同じ正規化をして保存されているので、そのまま一致で引ける
+    let (author, _) = normalize_author_fields(new_book.author.as_deref(), None);
+    let mut stmt = conn
+        .prepare("SELECT id, isbn, title, author FROM books WHERE deleted_at IS NULL AND author IS ?1")
+        .map_err(|e| e.to_string())?;
+    let candidates = stmt
+        .query_map([&author], |row| {
+            let isbn: Option<String> = row.get(1)?;
+            let title: String = row.get(2)?;
+            let author: Option<String> = row.get(3)?;
+            Ok((row.get::<_, i64>(0)?, MatchKey::new(isbn.as_deref(), &title, author.as_deref())))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    for (id, other) in candidates {
+        if matched.iter().any(|(matched_id, _)| *matched_id == id) {
+            continue;
+        }
+        if key.isbn.is_some() && other.isbn == key.isbn {
+            matched.push((id, DuplicateReason::Isbn));
+        } else if key.is_similar(&other) {
+            matched.push((id, DuplicateReason::TitleAuthor));
+        }
+    }
+
+    matched
+        .into_iter()
+        .map(|(id, reason)| Ok(DuplicateMatch { book: get_book(conn, id)?, reason }))
+        .collect()
+}
+
+/// 重複判定用に正規化した書誌情報
+struct MatchKey {
+    isbn: Option<String>,
+    author: String,
+    title: Vec<char>,
+    volume: Option<i64>,
+    /// 上・中・下
+    part: Option<char>,
+}
+
+impl MatchKey {
+    fn new(isbn: Option<&str>, title: &str, author: Option<&str>) -> Self {
+        let (title, part) = split_part(title);
+        let (base_title, volume) = split_volume(&title);
+        MatchKey {
+            isbn: isbn.and_then(normalize_isbn),
+            author: author.map(normalize_author).map(|a| comparable(&a)).unwrap_or_default(),
+            title: comparable(&strip_brackets(&base_title)).chars().collect(),
+            volume,
+            part,
+        }
+    }
+
+    /// 著者が同じ前提で、書名がほぼ同じで巻数も一致するか。ISBN が両方あって違えば別の版とみなす。
+    fn is_similar(&self, other: &MatchKey) -> bool {
+        if let (Some(a), Some(b)) = (&self.isbn, &other.isbn) {
+            if a != b {
+                return false;
+            }
+        }
+        !self.title.is_empty()
+            && self.volume == other.volume
+            && self.part == other.part
+            && similarity(&self.title, &other.title) >= TITLE_SIMILARITY_THRESHOLD
+    }
+}
+
+/// "こころ (上)" "こころ 下巻" のような上・中・下の巻を取り出す。
+/// 末尾の表記は書名から除き、括弧書きは `strip_brackets` に任せる。
+fn split_part(title: &str) -> (String, Option<char>) {
+    let text = unify_width(title);
+    let part_of = |s: &str| {
+        let s = s.trim();
+        match s.strip_suffix('巻').unwrap_or(s) {
+            "上" => Some('上'),
+            "中" => Some('中'),
+            "下" => Some('下'),
+            _ => None,
+        }
+    };
+    let mut depth = 0usize;
+    let mut inner = String::new();
+    for c in text.chars() {
+        match c {
+            '(' | '[' | '【' | '〔' => {
+                depth += 1;
+                inner.clear();
+            }
+            ')' | ']' | '】' | '〕' => {
+                if depth == 1 {
+                    if let Some(part) = part_of(&inner) {
+                        return (text, Some(part));
+                    }
+                }
+                depth = depth.saturating_sub(1);
+            }
+            _ if depth > 0 => inner.push(c),
+            _ => {}
+        }
+    }
+    if let Some((base, last)) = text.rsplit_once(' ') {
+        if let Some(part) = part_of(last) {
+            return (base.to_string(), Some(part));
+        }
+    }
+    for part in ['上', '中', '下'] {
+        if let Some(base) = text.strip_suffix(&format!("{}巻", part)) {
+            return (base.to_string(), Some(part));
+        }
+    }
+    (text, None)
+}
+
+/// 比較用に、幅と大文字小文字を揃えて文字と数字だけを残す。
+fn comparable(text: &str) -> String {
+    unify_width(text)
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+/// "こころ (新潮文庫)" のような括弧書きを取り除く。
+fn strip_brackets(text: &str) -> String {
+    let mut depth = 0usize;
+    let mut stripped = String::new();
+    for c in unify_width(text).chars() {
+        match c {
+            '(' | '[' | '【' | '〔' => depth += 1,
+            ')' | ']' | '】' | '〕' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
+}
+
+/// 1 - 編集距離 / 長い方の文字数
+fn similarity(a: &[char], b: &[char]) -> f64 {
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+fn find_root(parent: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    parent[i] = root;
+    root
+}
@@ -1,12 +1,15 @@
-use crate::db::DbConnection;
-use crate::models::Genre;
+use crate::db::{genre_subtree_ids, DbConnection};
+use crate::error::CommandError;
+use crate::models::{Genre, GenreNode};
+use rusqlite::Connection;
+use std::collections::HashMap;
 use tauri::State;
 
 #[tauri::command]
 pub fn get_genres(db: State<DbConnection>) -> Result<Vec<Genre>, String> {
     let conn = db.0.lock().unwrap();
     let mut stmt = conn
-        .prepare("SELECT id, name FROM genres ORDER BY name")
+        .prepare("SELECT id, name, parent_id, created_at, updated_at FROM genres ORDER BY name COLLATE KANA")
         .map_err(|e| e.to_string())?;
     let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
     let mut genres = Vec::new();
@@ -14,40 +17,196 @@ pub fn get_genres(db: State<DbConnection>) -> Result<Vec<Genre>, String> {
         genres.push(Genre {
             id: row.get(0).map_err(|e| e.to_string())?,
             name: row.get(1).map_err(|e| e.to_string())?,
+            parent_id: row.get(2).map_err(|e| e.to_string())?,
+            created_at: row.get(3).map_err(|e| e.to_string())?,
+            updated_at: row.get(4).map_err(|e| e.to_string())?,
         });
     }
     Ok(genres)
 }
 
+/// ジャンルをツリーで返す。各階層は名前の五十音順で、冊数は子孫ジャンルの分も合計する。
 #[tauri::command]
-pub fn add_genre(name: String, db: State<DbConnection>) -> Result<Genre, String> {
+pub fn get_genre_tree(db: State<DbConnection>) -> Result<Vec<GenreNode>, String> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn
+        .prepare(
+            "SELECT g.id, g.name, g.parent_id, COUNT(c.id)
+             FROM genres g
+             LEFT JOIN books b ON b.genre_id = g.id AND b.deleted_at IS NULL
+             LEFT JOIN copies c ON c.book_id = b.id
+             GROUP BY g.id
+             ORDER BY g.name COLLATE KANA",
+        )
+        .map_err(|e| e.to_string())?;
+    let nodes = stmt
+        .query_map([], |row| {
+            Ok(GenreNode {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                parent_id: row.get(2)?,
+                book_count: row.get(3)?,
+                total_book_count: 0,
+                children: Vec::new(),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    // 親が存在しないジャンルは最上位として扱う
+    let ids: Vec<i64> = nodes.iter().map(|node| node.id).collect();
+    let mut children: HashMap<Option<i64>, Vec<GenreNode>> = HashMap::new();
+    for node in nodes {
+        let parent = node.parent_id.filter(|id| ids.contains(id));
+        children.entry(parent).or_default().push(node);
+    }
+    Ok(build_tree(None, &mut children))
+}
+
+fn build_tree(
+    parent: Option<i64>,
+    children: &mut HashMap<Option<i64>, Vec<GenreNode>>,
+) -> Vec<GenreNode> {
+    let mut nodes = children.remove(&parent).unwrap_or_default();
+    for node in &mut nodes {
+        node.children = build_tree(Some(node.id), children);
+        node.total_book_count =
+            node.book_count + node.children.iter().map(|c| c.total_book_count).sum::<i64>();
+    }
+    nodes
+}
+
+/// ジャンルを追加する。同じ親の下に同じ名前があればそれを返す。
+/// 名前はジャンル全体で一意なので、別の親の下にあれば `conflict` エラーを返す。
+#[tauri::command]
+pub fn add_genre(
+    name: String,
+    parent_id: Option<i64>,
+    db: State<DbConnection>,
+) -> Result<Genre, CommandError> {
     if name.trim().is_empty() {
-        return Err("ジャンル名が空です".into());
+        return Err(CommandError::Invalid("ジャンル名が空です".into()));
+    }
+    let conn = db.0.lock().unwrap();
+    if let Some(parent_id) = parent_id {
+        ensure_genre_exists(&conn, parent_id)?;
+    }
+    conn.execute(
+        "INSERT OR IGNORE INTO genres (name, parent_id) VALUES (?1, ?2)",
+        rusqlite::params![name, parent_id],
+    )?;
+    let genre = conn.query_row(
+        "SELECT id, name, parent_id, created_at, updated_at FROM genres WHERE name = ?1",
+        [&name],
+        |row| {
+            Ok(Genre {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                parent_id: row.get(2)?,
+                created_at: row.get(3)?,
+                updated_at: row.get(4)?,
+            })
+        },
+    )?;
+    if genre.parent_id != parent_id {
+        return Err(CommandError::Conflict(format!(
+            "ジャンル「{}」は別の場所に既に存在します",
+            name
+        )));
     }
+    Ok(genre)
+}
+
+/// ジャンルを子孫ごと別の親の下へ移す。`parent_id` が None なら最上位に移す。
+#[tauri::command]
+pub fn move_genre(id: i64, parent_id: Option<i64>, db: State<DbConnection>) -> Result<(), String> {
     let conn = db.0.lock().unwrap();
+    ensure_genre_exists(&conn, id).map_err(|e| e.to_string())?;
+    if let Some(parent_id) = parent_id {
+        ensure_genre_exists(&conn, parent_id).map_err(|e| e.to_string())?;
+        let subtree = genre_subtree_ids(&conn, id).map_err(|e| e.to_string())?;
+        if subtree.contains(&parent_id) {
+            return Err("ジャンルを自身または子孫のジャンルの下へは移動できません".into());
+        }
+    }
     conn.execute(
-        "INSERT OR IGNORE INTO genres (name) VALUES (?1)",
-        rusqlite::params![name],
+        "UPDATE genres SET parent_id = ?1 WHERE id = ?2",
+        rusqlite::params![parent_id, id],
     )
     .map_err(|e| e.to_string())?;
-    {
-        let mut stmt = conn
-            .prepare("SELECT id, name FROM genres WHERE name = ?1")
-            .map_err(|e| e.to_string())?;
-        let genre = stmt
-            .query_row([&name], |row| {
-                Ok(Genre {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                })
+    Ok(())
+}
+
+/// 名前が他のジャンルと重複する場合は `conflict` エラーを返す。
+#[tauri::command]
+pub fn rename_genre(id: i64, name: String, db: State<DbConnection>) -> Result<Genre, CommandError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CommandError::Invalid("ジャンル名が空です".into()));
+    }
+    let conn = db.0.lock().unwrap();
+    let affected = conn
+        .execute("UPDATE genres SET name = ?1 WHERE id = ?2", rusqlite::params![name, id])
+        .map_err(|e| {
+            CommandError::from_unique_violation(e, || format!("ジャンル「{}」は既に存在します", name))
+        })?;
+    if affected == 0 {
+        return Err(CommandError::NotFound(format!("Genre with id {} not found", id)));
+    }
+    let genre = conn.query_row(
+        "SELECT id, name, parent_id, created_at, updated_at FROM genres WHERE id = ?1",
+        [id],
+        |row| {
+            Ok(Genre {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                parent_id: row.get(2)?,
+                created_at: row.get(3)?,
+                updated_at: row.get(4)?,
             })
-            .map_err(|e| e.to_string())?;
-        Ok(genre)
+        },
+    )?;
+    Ok(genre)
+}
+
+/// `source_id` の書籍と下位ジャンルを `target_id` に移し、`source_id` を削除する。
+#[tauri::command]
+pub fn merge_genres(
+    source_id: i64,
+    target_id: i64,
+    db: State<DbConnection>,
+) -> Result<(), CommandError> {
+    if source_id == target_id {
+        return Err(CommandError::Invalid("同じジャンルは統合できません".into()));
     }
+    let mut conn = db.0.lock().unwrap();
+    ensure_genre_exists(&conn, source_id)?;
+    ensure_genre_exists(&conn, target_id)?;
+    if genre_subtree_ids(&conn, source_id)?.contains(&target_id) {
+        return Err(CommandError::Invalid(
+            "ジャンルを自身の下位ジャンルへは統合できません".into(),
+        ));
+    }
+    crate::db::merge_genres(&mut conn, source_id, target_id)?;
+    Ok(())
 }
 
+/// 子孫ジャンルもまとめて削除し、それらの書籍は「未分類」に移す。
 #[tauri::command]
 pub fn delete_genre(id: i64, db: State<DbConnection>) -> Result<(), String> {
     let mut conn = db.0.lock().unwrap();
     crate::db::delete_genre_and_unassign_books(&mut conn, id).map_err(|e| e.to_string())
 }
+
+fn ensure_genre_exists(conn: &Connection, id: i64) -> Result<(), CommandError> {
+    let exists: bool =
+        conn.query_row("SELECT EXISTS(SELECT 1 FROM genres WHERE id = ?1)", [id], |row| {
+            row.get(0)
+        })?;
+    if exists {
+        Ok(())
+    } else {
+        Err(CommandError::NotFound(format!("Genre with id {} not found", id)))
+    }
+}
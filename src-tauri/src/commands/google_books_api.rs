@@ -1,4 +1,5 @@
 use crate::models::BookInfoFromApi;
+use crate::normalize::{normalize_author, normalize_isbn, resolve_series, AUTHOR_SEPARATOR};
 use serde_json::Value;
 
 #[tauri::command]
@@ -54,10 +55,10 @@ fn parse_item(item: &Value) -> Option<BookInfoFromApi> {
         .map(|authors| {
             authors
                 .iter()
-                .filter_map(|v| v.as_str().map(str::trim))
+                .filter_map(|v| v.as_str().map(normalize_author))
                 .filter(|name| !name.is_empty())
                 .collect::<Vec<_>>()
-                .join(", ")
+                .join(AUTHOR_SEPARATOR)
         })
         .unwrap_or_default();
 
@@ -67,9 +68,29 @@ fn parse_item(item: &Value) -> Option<BookInfoFromApi> {
         .unwrap_or("")
         .to_string();
 
+    let (series, volume) = resolve_series(&title, None, None);
+    let isbn = volume_info["industryIdentifiers"].as_array().and_then(|ids| {
+        ids.iter()
+            .filter(|id| matches!(id["type"].as_str(), Some("ISBN_13") | Some("ISBN_10")))
+            .find_map(|id| id["identifier"].as_str().and_then(normalize_isbn))
+    });
+
+    // 大きい順に探す。http で返ってくることがあるので https に揃える
+    let image_links = &volume_info["imageLinks"];
+    let cover_url = ["extraLarge", "large", "medium", "small", "thumbnail", "smallThumbnail"]
+        .iter()
+        .find_map(|size| image_links[size].as_str())
+        .map(|url| url.replacen("http://", "https://", 1));
+
     Some(BookInfoFromApi {
         title,
+        title_kana: None,
         author,
+        author_kana: None,
         publisher,
+        series,
+        volume,
+        isbn,
+        cover_url,
     })
 }
@@ -0,0 +1,194 @@
+use crate::commands::book::{get_book, trash_book};
+use crate::db::DbConnection;
+use crate::error::CommandError;
+use crate::history::{restore_book, revert_book};
+use crate::models::{Book, BookHistoryEntry, HistoryAction};
+use rusqlite::{Connection, OptionalExtension};
+use tauri::State;
+
+const HISTORY_COLUMNS: &str = "id, book_id, action, title, before, after, changed_at, undone_at";
+
+/// 本の変更履歴を新しい順に返す。
+#[tauri::command]
+pub fn get_book_history(book_id: i64, db: State<DbConnection>) -> Result<Vec<BookHistoryEntry>, String> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM book_history WHERE book_id = ?1 ORDER BY id DESC",
+            HISTORY_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([book_id], row_to_history_entry)
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// まだ取り消していない最後の変更を取り消し、取り消した履歴を返す。
+/// 追加は本をゴミ箱に移し（削除の履歴が残る）、変更は書誌情報・シリーズ・タグ・既読を戻し、削除は本を元に戻す。
+#[tauri::command]
+pub fn undo_last_change(db: State<DbConnection>) -> Result<BookHistoryEntry, CommandError> {
+    let mut conn = db.0.lock().unwrap();
+    let entry = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM book_history WHERE undone_at IS NULL ORDER BY id DESC LIMIT 1",
+                HISTORY_COLUMNS
+            ),
+            [],
+            row_to_history_entry,
+        )
+        .optional()?
+        .ok_or_else(|| CommandError::NotFound("取り消せる変更がありません".into()))?;
+
+    let tx = conn.transaction()?;
+    match entry.action {
+        HistoryAction::Insert => {
+            // 追加した後に付けた読書記録やメモもあるので、完全には消さずゴミ箱に移す
+            if !trash_book(&tx, entry.book_id)? {
+                return Err(CommandError::Conflict(format!(
+                    "「{}」は既に削除されているため、追加を取り消せません",
+                    entry.title
+                )));
+            }
+            mark_undone(&tx, entry.id)?;
+        }
+        HistoryAction::Update => {
+            let book_exists: bool = tx.query_row(
+                "SELECT EXISTS(SELECT 1 FROM books WHERE id = ?1 AND deleted_at IS NULL)",
+                [entry.book_id],
+                |row| row.get(0),
+            )?;
+            if !book_exists {
+                return Err(CommandError::Conflict(format!(
+                    "「{}」は既に削除されているため、変更を取り消せません",
+                    entry.title
+                )));
+            }
+            let before = entry.before.as_ref().ok_or_else(broken_entry)?;
+            revert_book(&tx, entry.book_id, before).map_err(CommandError::Database)?;
+            mark_undone(&tx, entry.id)?;
+        }
+        HistoryAction::Delete => {
+            restore_deleted(&tx, &entry)?;
+        }
+    }
+    tx.commit()?;
+    get_history_entry(&conn, entry.id)
+}
+
+/// 削除した本を、最後に削除したときの状態に戻す。ゴミ箱にあればそこから戻し、
+/// 完全に削除した後なら履歴のスナップショットから作り直す（元の id が他の本に使われていれば新しい id で）。
+#[tauri::command]
+pub fn restore_deleted_book(book_id: i64, db: State<DbConnection>) -> Result<Book, CommandError> {
+    let mut conn = db.0.lock().unwrap();
+    let entry = last_delete_entry(&conn, book_id)?
+        .ok_or_else(|| CommandError::NotFound(format!("削除した本 (id {}) の履歴がありません", book_id)))?;
+
+    let tx = conn.transaction()?;
+    let restored_id = restore_deleted(&tx, &entry)?;
+    tx.commit()?;
+    get_book(&conn, restored_id).map_err(CommandError::Database)
+}
+
+/// 削除の履歴 `entry` の本を戻して、その履歴を取り消し済みにする。戻した本の id を返す。
+pub(crate) fn restore_deleted(conn: &Connection, entry: &BookHistoryEntry) -> Result<i64, CommandError> {
+    let untrashed = conn.execute(
+        "UPDATE books SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+        [entry.book_id],
+    )?;
+    let book_id = if untrashed > 0 {
+        entry.book_id
+    } else {
+        let before = entry.before.as_ref().ok_or_else(broken_entry)?;
+        let book_id = restore_book(conn, before).map_err(CommandError::Database)?;
+        reassign_history(conn, entry.book_id, book_id, entry.id)?;
+        book_id
+    };
+    mark_undone(conn, entry.id)?;
+    Ok(book_id)
+}
+
+/// 本を最後に削除したときの、まだ取り消していない履歴
+pub(crate) fn last_delete_entry(
+    conn: &Connection,
+    book_id: i64,
+) -> Result<Option<BookHistoryEntry>, rusqlite::Error> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM book_history
+             WHERE book_id = ?1 AND action = 'delete' AND undone_at IS NULL
+             ORDER BY id DESC LIMIT 1",
+            HISTORY_COLUMNS
+        ),
+        [book_id],
+        row_to_history_entry,
+    )
+    .optional()
+}
+
+/// 別の id で戻した本に、それまでの履歴を付け替える。
+fn reassign_history(
+    conn: &Connection,
+    old_id: i64,
+    new_id: i64,
+    last_entry_id: i64,
+) -> Result<(), rusqlite::Error> {
+    if old_id != new_id {
+        conn.execute(
+            "UPDATE book_history SET book_id = ?1 WHERE book_id = ?2 AND id <= ?3",
+            [new_id, old_id, last_entry_id],
+        )?;
+    }
+    Ok(())
+}
+
+fn mark_undone(conn: &Connection, entry_id: i64) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "UPDATE book_history SET undone_at = datetime('now', 'localtime') WHERE id = ?1",
+        [entry_id],
+    )?;
+    Ok(())
+}
+
+fn get_history_entry(conn: &Connection, id: i64) -> Result<BookHistoryEntry, CommandError> {
+    Ok(conn.query_row(
+        &format!("SELECT {} FROM book_history WHERE id = ?1", HISTORY_COLUMNS),
+        [id],
+        row_to_history_entry,
+    )?)
+}
+
+fn broken_entry() -> CommandError {
+    CommandError::Database("履歴のスナップショットが壊れています".into())
+}
+
+fn row_to_history_entry(row: &rusqlite::Row) -> rusqlite::Result<BookHistoryEntry> {
+    let action: String = row.get(2)?;
+    let action = HistoryAction::parse(&action).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            2,
+            rusqlite::types::Type::Text,
+            format!("unknown history action: {}", action).into(),
+        )
+    })?;
+    Ok(BookHistoryEntry {
+        id: row.get(0)?,
+        book_id: row.get(1)?,
+        action,
+        title: row.get(3)?,
+        before: snapshot(row, 4)?,
+        after: snapshot(row, 5)?,
+        changed_at: row.get(6)?,
+        undone_at: row.get(7)?,
+    })
+}
+
+fn snapshot(row: &rusqlite::Row, idx: usize) -> rusqlite::Result<Option<serde_json::Value>> {
+    let Some(text) = row.get::<_, Option<String>>(idx)? else {
+        return Ok(None);
+    };
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, e.into()))
+}
@@ -0,0 +1,185 @@
+use crate::commands::book::{row_to_book, BOOK_COLUMNS};
+use crate::commands::copy::get_copy;
+use crate::commands::reading::normalize_date;
+use crate::db::DbConnection;
+use crate::error::CommandError;
+use crate::models::{Loan, OutstandingLoan};
+use rusqlite::{Connection, OptionalExtension};
+use tauri::State;
+
+const LOAN_COLUMNS: &str = "id, book_id, copy_id, borrower, lent_at, due_at, returned_at";
+
+/// 本を貸し出す。`lent_at` を省略すると今日の日付になる。
+/// `copy_id` を省略すると貸出中でない冊を選ぶ。空いている冊がなければ `conflict` エラーになる。
+#[tauri::command]
+pub fn lend_book(
+    book_id: i64,
+    copy_id: Option<i64>,
+    borrower: String,
+    lent_at: Option<String>,
+    due_at: Option<String>,
+    db: State<DbConnection>,
+) -> Result<Loan, CommandError> {
+    let borrower = borrower.trim();
+    if borrower.is_empty() {
+        return Err(CommandError::Invalid("借りる人の名前が空です".into()));
+    }
+    let conn = db.0.lock().unwrap();
+    let book_exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM books WHERE id = ?1 AND deleted_at IS NULL)",
+        [book_id],
+        |row| row.get(0),
+    )?;
+    if !book_exists {
+        return Err(CommandError::NotFound(format!("Book with id {} not found", book_id)));
+    }
+    let lent_at = normalize_date(&conn, lent_at.as_deref()).map_err(CommandError::Invalid)?;
+    let due_at = due_at
+        .filter(|date| !date.trim().is_empty())
+        .map(|date| normalize_date(&conn, Some(&date)))
+        .transpose()
+        .map_err(CommandError::Invalid)?;
+    if due_at.as_deref().is_some_and(|due_at| due_at < lent_at.as_str()) {
+        return Err(CommandError::Invalid("返却期限が貸出日より前です".into()));
+    }
+    let copy_id = match copy_id {
+        Some(copy_id) => {
+            let copy = get_copy(&conn, copy_id).map_err(CommandError::NotFound)?;
+            if copy.book_id != book_id {
+                return Err(CommandError::Invalid("指定した冊はこの本のものではありません".into()));
+            }
+            copy_id
+        }
+        None => conn
+            .query_row(
+                "SELECT id FROM copies
+                 WHERE book_id = ?1
+                   AND NOT EXISTS (SELECT 1 FROM loans
+                                   WHERE copy_id = copies.id AND returned_at IS NULL)
+                 ORDER BY id LIMIT 1",
+                [book_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| CommandError::Conflict("この本は貸出中です".into()))?,
+    };
+    conn.execute(
+        "INSERT INTO loans (book_id, copy_id, borrower, lent_at, due_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![book_id, copy_id, borrower, lent_at, due_at],
+    )
+    .map_err(|e| CommandError::from_unique_violation(e, || "この本は貸出中です".to_string()))?;
+    get_loan(&conn, conn.last_insert_rowid())
+}
+
+/// 返却を記録する。`returned_at` を省略すると今日の日付になる。
+#[tauri::command]
+pub fn return_book(
+    loan_id: i64,
+    returned_at: Option<String>,
+    db: State<DbConnection>,
+) -> Result<Loan, CommandError> {
+    let conn = db.0.lock().unwrap();
+    let loan = get_loan(&conn, loan_id)?;
+    if loan.returned_at.is_some() {
+        return Err(CommandError::Invalid("この貸出は返却済みです".into()));
+    }
+    let returned_at =
+        normalize_date(&conn, returned_at.as_deref()).map_err(CommandError::Invalid)?;
+    if returned_at < loan.lent_at {
+        return Err(CommandError::Invalid("返却日が貸出日より前です".into()));
+    }
+    conn.execute(
+        "UPDATE loans SET returned_at = ?1 WHERE id = ?2",
+        rusqlite::params![returned_at, loan_id],
+    )?;
+    get_loan(&conn, loan_id)
+}
+
+/// 貸出中の本を、返却期限の近い順（期限なしは最後）で返す。
+#[tauri::command]
+pub fn get_outstanding_loans(db: State<DbConnection>) -> Result<Vec<OutstandingLoan>, String> {
+    let conn = db.0.lock().unwrap();
+    query_outstanding(&conn, false).map_err(|e| e.to_string())
+}
+
+/// 返却期限を過ぎても返却されていない貸出
+#[tauri::command]
+pub fn get_overdue_loans(db: State<DbConnection>) -> Result<Vec<OutstandingLoan>, String> {
+    let conn = db.0.lock().unwrap();
+    query_outstanding(&conn, true).map_err(|e| e.to_string())
+}
+
+/// 本の貸出履歴を新しい順で返す。
+#[tauri::command]
+pub fn get_loan_history(book_id: i64, db: State<DbConnection>) -> Result<Vec<Loan>, String> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM loans WHERE book_id = ?1 ORDER BY lent_at DESC, id DESC",
+            LOAN_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([book_id], row_to_loan)
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+fn query_outstanding(
+    conn: &Connection,
+    overdue_only: bool,
+) -> Result<Vec<OutstandingLoan>, rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, loan_id, book_id, copy_id, borrower, lent_at, due_at, returned_at,
+                due_at < date('now', 'localtime') AS is_overdue
+         FROM books
+         JOIN (SELECT id AS loan_id, book_id, copy_id, borrower, lent_at, due_at, returned_at
+               FROM loans WHERE returned_at IS NULL) l ON l.book_id = books.id
+         WHERE books.deleted_at IS NULL {}
+         ORDER BY due_at IS NULL, due_at, lent_at, loan_id",
+        BOOK_COLUMNS,
+        if overdue_only {
+            "AND due_at < date('now', 'localtime')"
+        } else {
+            ""
+        }
+    ))?;
+    let rows = stmt.query_map([], |row| {
+        Ok(OutstandingLoan {
+            loan: Loan {
+                id: row.get("loan_id")?,
+                book_id: row.get("book_id")?,
+                copy_id: row.get("copy_id")?,
+                borrower: row.get("borrower")?,
+                lent_at: row.get("lent_at")?,
+                due_at: row.get("due_at")?,
+                returned_at: row.get("returned_at")?,
+            },
+            book: row_to_book(row)?,
+            is_overdue: row.get::<_, Option<bool>>("is_overdue")?.unwrap_or(false),
+        })
+    })?;
+    rows.collect()
+}
+
+fn get_loan(conn: &Connection, loan_id: i64) -> Result<Loan, CommandError> {
+    conn.query_row(
+        &format!("SELECT {} FROM loans WHERE id = ?1", LOAN_COLUMNS),
+        [loan_id],
+        row_to_loan,
+    )
+    .optional()?
+    .ok_or_else(|| CommandError::NotFound(format!("Loan with id {} not found", loan_id)))
+}
+
+fn row_to_loan(row: &rusqlite::Row) -> rusqlite::Result<Loan> {
+    Ok(Loan {
+        id: row.get(0)?,
+        book_id: row.get(1)?,
+        copy_id: row.get(2)?,
+        borrower: row.get(3)?,
+        lent_at: row.get(4)?,
+        due_at: row.get(5)?,
+        returned_at: row.get(6)?,
+    })
+}
@@ -0,0 +1,362 @@
+use crate::collation::compare_kana;
+use crate::commands::book::{query_books, row_to_book, BOOK_COLUMNS};
+use crate::commands::copy::acquisition_kind;
+use crate::db::{DbConnection, LOCATION_SUBTREE_CTE};
+use crate::error::CommandError;
+use crate::history::{record_update, snapshot_book};
+use crate::models::{
+    Book, BookSort, Copy, CopyLocation, Location, LocationKind, StocktakeResult,
+};
+use crate::normalize::normalize_isbn;
+use rusqlite::{Connection, OptionalExtension};
+use std::collections::HashMap;
+use tauri::State;
+
+/// 場所の一覧をツリーの順（各階層は五十音順）で返す。
+#[tauri::command]
+pub fn get_locations(db: State<DbConnection>) -> Result<Vec<Location>, String> {
+    let conn = db.0.lock().unwrap();
+    load_locations(&conn).map_err(|e| e.to_string())
+}
+
+/// 建物は最上位に、部屋は建物の下に、棚は部屋の下に作る。
+#[tauri::command]
+pub fn add_location(
+    name: String,
+    kind: LocationKind,
+    parent_id: Option<i64>,
+    db: State<DbConnection>,
+) -> Result<Location, CommandError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CommandError::Invalid("場所の名前が空です".into()));
+    }
+    let conn = db.0.lock().unwrap();
+    let parent_kind = match parent_id {
+        Some(parent_id) => Some(location_kind(&conn, parent_id)?),
+        None => None,
+    };
+    if parent_kind != kind.parent_kind() {
+        return Err(CommandError::Invalid(
+            "場所は 建物 > 部屋 > 棚 の順に作成してください".into(),
+        ));
+    }
+    // UNIQUE (parent_id, name) は NULL 同士を区別しないので、建物の重複はここで弾く
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM locations WHERE parent_id IS ?1 AND name = ?2)",
+        rusqlite::params![parent_id, name],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Err(CommandError::Conflict(format!("場所「{}」は既に存在します", name)));
+    }
+    conn.execute(
+        "INSERT INTO locations (name, kind, parent_id) VALUES (?1, ?2, ?3)",
+        rusqlite::params![name, kind.as_str(), parent_id],
+    )?;
+    find_location(&conn, conn.last_insert_rowid())
+}
+
+#[tauri::command]
+pub fn rename_location(
+    id: i64,
+    name: String,
+    db: State<DbConnection>,
+) -> Result<Location, CommandError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CommandError::Invalid("場所の名前が空です".into()));
+    }
+    let conn = db.0.lock().unwrap();
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM locations
+                       WHERE parent_id IS (SELECT parent_id FROM locations WHERE id = ?1)
+                         AND name = ?2 AND id <> ?1)",
+        rusqlite::params![id, name],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Err(CommandError::Conflict(format!("場所「{}」は既に存在します", name)));
+    }
+    let affected = conn.execute(
+        "UPDATE locations SET name = ?1 WHERE id = ?2",
+        rusqlite::params![name, id],
+    )?;
+    if affected == 0 {
+        return Err(CommandError::NotFound(format!("Location with id {} not found", id)));
+    }
+    find_location(&conn, id)
+}
+
+/// 場所を削除する。そこにあった冊は置き場所なしになる。下位の場所があれば削除しない。
+#[tauri::command]
+pub fn delete_location(id: i64, db: State<DbConnection>) -> Result<(), String> {
+    let mut conn = db.0.lock().unwrap();
+    let has_children: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM locations WHERE parent_id = ?1)",
+            [id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if has_children {
+        return Err("下位の場所があるため削除できません".into());
+    }
+    match crate::db::delete_location(&mut conn, id) {
+        Ok(affected) if affected > 0 => Ok(()),
+        Ok(_) => Err(format!("Location with id {} not found", id)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// 選択した本をまとめて移動する。複数冊ある本はすべての冊を移す。
+/// `location_id` が None なら置き場所なしにする。
+#[tauri::command]
+pub fn move_books_to_location(
+    book_ids: Vec<i64>,
+    location_id: Option<i64>,
+    db: State<DbConnection>,
+) -> Result<(), String> {
+    let mut conn = db.0.lock().unwrap();
+    if let Some(location_id) = location_id {
+        location_kind(&conn, location_id).map_err(|e| e.to_string())?;
+    }
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for &book_id in &book_ids {
+        let before = snapshot_book(&tx, book_id).map_err(|e| e.to_string())?;
+        let affected = tx
+            .execute(
+                "UPDATE copies SET location_id = ?1 WHERE book_id = ?2",
+                rusqlite::params![location_id, book_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if affected == 0 {
+            return Err(format!("Book with id {} not found", book_id));
+        }
+        record_update(&tx, book_id, before.as_ref()).map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// 場所にある本。部屋や建物を指定した場合は、既定でその下の棚にある本も含める。
+#[tauri::command]
+pub fn get_location_books(
+    location_id: i64,
+    include_descendants: Option<bool>,
+    sort: Option<BookSort>,
+    db: State<DbConnection>,
+) -> Result<Vec<Book>, String> {
+    let conn = db.0.lock().unwrap();
+    let conditions = vec![format!(
+        "id IN (SELECT book_id FROM copies WHERE {})",
+        location_condition(include_descendants.unwrap_or(true))
+    )];
+    query_books(&conn, &conditions, &[&location_id], sort)
+}
+
+/// ISBN の本が1冊ずつどこにあるかを返す。ハイフンの有無や ISBN-10/13 の違いは区別しない。
+#[tauri::command]
+pub fn find_books_by_isbn(isbn: String, db: State<DbConnection>) -> Result<Vec<CopyLocation>, String> {
+    let conn = db.0.lock().unwrap();
+    let key = isbn_key(&isbn);
+    let locations = locations_by_id(&conn).map_err(|e| e.to_string())?;
+    Ok(copies_with_isbn(&conn, &locations)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|(copy_key, _)| *copy_key == key)
+        .map(|(_, copy)| copy)
+        .collect())
+}
+
+/// 棚卸し。読み取った ISBN と、その場所（下位の場所を含む）にあるはずの冊を突き合わせる。
+/// 同じ ISBN を2回読み取れば2冊目として数える。
+#[tauri::command]
+pub fn stocktake(
+    location_id: i64,
+    scanned_isbns: Vec<String>,
+    db: State<DbConnection>,
+) -> Result<StocktakeResult, String> {
+    let conn = db.0.lock().unwrap();
+    location_kind(&conn, location_id).map_err(|e| e.to_string())?;
+    let locations = locations_by_id(&conn).map_err(|e| e.to_string())?;
+    let mut expected: Vec<(String, CopyLocation)> = query_copy_locations(
+        &conn,
+        &location_condition(true),
+        &[&location_id],
+        &locations,
+    )
+    .map_err(|e| e.to_string())?
+    .into_iter()
+    .map(|copy| (copy.book.isbn.as_deref().map(isbn_key).unwrap_or_default(), copy))
+    .collect();
+    let mut elsewhere: Vec<(String, CopyLocation)> = copies_with_isbn(&conn, &locations)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|(_, copy)| !expected.iter().any(|(_, e)| e.copy.id == copy.copy.id))
+        .collect();
+
+    let mut found = Vec::new();
+    let mut misplaced = Vec::new();
+    let mut unknown_isbns = Vec::new();
+    for scanned in scanned_isbns.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        let key = isbn_key(scanned);
+        if let Some(i) = expected.iter().position(|(k, _)| *k == key) {
+            found.push(expected.remove(i).1);
+        } else if let Some(i) = elsewhere.iter().position(|(k, _)| *k == key) {
+            misplaced.push(elsewhere.remove(i).1);
+        } else if !unknown_isbns.contains(&key) {
+            unknown_isbns.push(key);
+        }
+    }
+    Ok(StocktakeResult {
+        location_id,
+        found,
+        missing: expected.into_iter().map(|(_, copy)| copy).collect(),
+        misplaced,
+        unknown_isbns,
+    })
+}
+
+/// `?1` の場所（と下位の場所）に置かれた冊を絞り込む条件
+fn location_condition(include_descendants: bool) -> String {
+    if include_descendants {
+        format!(
+            "location_id IN ({} SELECT id FROM location_subtree)",
+            LOCATION_SUBTREE_CTE
+        )
+    } else {
+        "location_id = ?1".to_string()
+    }
+}
+
+/// 突き合わせ用の ISBN。13桁に揃えられなければ数字だけを使う。
+fn isbn_key(isbn: &str) -> String {
+    normalize_isbn(isbn).unwrap_or_else(|| isbn.chars().filter(char::is_ascii_alphanumeric).collect())
+}
+
+fn copies_with_isbn(
+    conn: &Connection,
+    locations: &HashMap<i64, Location>,
+) -> Result<Vec<(String, CopyLocation)>, rusqlite::Error> {
+    let copies = query_copy_locations(
+        conn,
+        "book_id IN (SELECT id FROM books WHERE isbn IS NOT NULL AND isbn <> '')",
+        &[],
+        locations,
+    )?;
+    Ok(copies
+        .into_iter()
+        .map(|copy| (copy.book.isbn.as_deref().map(isbn_key).unwrap_or_default(), copy))
+        .collect())
+}
+
+/// 冊ごとに本と置き場所を付けて返す。`condition` は copies の列に対する条件。
+fn query_copy_locations(
+    conn: &Connection,
+    condition: &str,
+    params: &[&dyn rusqlite::ToSql],
+    locations: &HashMap<i64, Location>,
+) -> Result<Vec<CopyLocation>, rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, copy_id, book_id, condition, acquired_at, purchase_price, store, acquisition,
+                copy_location_id, notes
+         FROM books
+         JOIN (SELECT id AS copy_id, book_id, condition, acquired_at, purchase_price, store,
+                      acquisition, location_id AS copy_location_id, notes
+               FROM copies WHERE {}) c ON c.book_id = books.id
+         WHERE books.deleted_at IS NULL
+         ORDER BY COALESCE(title_kana, title) COLLATE KANA, copy_id",
+        BOOK_COLUMNS, condition
+    ))?;
+    let rows = stmt.query_map(params, |row| {
+        let copy = Copy {
+            id: row.get("copy_id")?,
+            book_id: row.get("book_id")?,
+            condition: row.get("condition")?,
+            acquired_at: row.get("acquired_at")?,
+            purchase_price: row.get("purchase_price")?,
+            store: row.get("store")?,
+            acquisition: acquisition_kind(row, row.as_ref().column_index("acquisition")?)?,
+            location_id: row.get("copy_location_id")?,
+            notes: row.get("notes")?,
+        };
+        Ok(CopyLocation {
+            book: row_to_book(row)?,
+            location: copy.location_id.and_then(|id| locations.get(&id)).cloned(),
+            copy,
+        })
+    })?;
+    rows.collect()
+}
+
+pub(crate) fn location_kind(conn: &Connection, id: i64) -> Result<LocationKind, CommandError> {
+    let kind: Option<String> = conn
+        .query_row("SELECT kind FROM locations WHERE id = ?1", [id], |row| row.get(0))
+        .optional()?;
+    kind.as_deref()
+        .and_then(LocationKind::parse)
+        .ok_or_else(|| CommandError::NotFound(format!("Location with id {} not found", id)))
+}
+
+fn find_location(conn: &Connection, id: i64) -> Result<Location, CommandError> {
+    locations_by_id(conn)?
+        .remove(&id)
+        .ok_or_else(|| CommandError::NotFound(format!("Location with id {} not found", id)))
+}
+
+fn locations_by_id(conn: &Connection) -> Result<HashMap<i64, Location>, rusqlite::Error> {
+    Ok(load_locations(conn)?
+        .into_iter()
+        .map(|location| (location.id, location))
+        .collect())
+}
+
+/// すべての場所を、表示用のパスを付けてツリーの順に並べる。
+fn load_locations(conn: &Connection) -> Result<Vec<Location>, rusqlite::Error> {
+    let mut stmt = conn.prepare("SELECT id, name, kind, parent_id FROM locations")?;
+    let rows = stmt.query_map([], |row| {
+        let kind: String = row.get(2)?;
+        Ok(Location {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            kind: LocationKind::parse(&kind).ok_or_else(|| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    2,
+                    rusqlite::types::Type::Text,
+                    format!("unknown location kind: {}", kind).into(),
+                )
+            })?,
+            parent_id: row.get(3)?,
+            path: String::new(),
+        })
+    })?;
+    let mut children: HashMap<Option<i64>, Vec<Location>> = HashMap::new();
+    for location in rows {
+        let location = location?;
+        children.entry(location.parent_id).or_default().push(location);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|a, b| compare_kana(&a.name, &b.name));
+    }
+    let mut ordered = Vec::new();
+    push_subtree(None, "", &mut children, &mut ordered);
+    Ok(ordered)
+}
+
+fn push_subtree(
+    parent: Option<i64>,
+    parent_path: &str,
+    children: &mut HashMap<Option<i64>, Vec<Location>>,
+    ordered: &mut Vec<Location>,
+) {
+    for mut location in children.remove(&parent).unwrap_or_default() {
+        location.path = if parent_path.is_empty() {
+            location.name.clone()
+        } else {
+            format!("{} > {}", parent_path, location.name)
+        };
+        let (id, path) = (location.id, location.path.clone());
+        ordered.push(location);
+        push_subtree(Some(id), &path, children, ordered);
+    }
+}
@@ -0,0 +1,398 @@
+use crate::commands::book::{check_revision, get_book, query_books, write_book};
+use crate::commands::google_books_api::fetch_book_info_from_google_books;
+use crate::commands::ndl_api::fetch_book_info_from_ndl;
+use crate::commands::rakuten_books_api::fetch_book_info_from_rakuten;
+use crate::db::DbConnection;
+use crate::error::CommandError;
+use crate::models::{
+    AcceptedMetadata, Book, BookField, BookInfoFromApi, MetadataChange, MetadataFillProgress,
+    MetadataFillSummary, MetadataProvider, MetadataRefresh, ProviderSettings, UpdateBook,
+};
+use crate::normalize::normalize_isbn;
+use rusqlite::Connection;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// 取り直しの対象にする項目
+const REFRESH_FIELDS: &[BookField] = &[
+    BookField::Title,
+    BookField::TitleKana,
+    BookField::Author,
+    BookField::AuthorKana,
+    BookField::Publisher,
+];
+
+const DEFAULT_PRIORITY: &[MetadataProvider] = &[
+    MetadataProvider::Ndl,
+    MetadataProvider::Google,
+    MetadataProvider::Rakuten,
+];
+
+/// 続けて問い合わせるときの間隔。提供元に負荷をかけすぎないよう空ける。
+const LOOKUP_INTERVAL: Duration = Duration::from_millis(500);
+
+pub const FILL_PROGRESS_EVENT: &str = "metadata-fill-progress";
+pub const FILL_FINISHED_EVENT: &str = "metadata-fill-finished";
+
+static FILL_RUNNING: AtomicBool = AtomicBool::new(false);
+static FILL_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// 本の ISBN で書誌情報を取り直し、登録内容と異なる項目を本ごとに返す。ここでは書き換えない。
+/// ISBN が無い本や取得できなかった本は `error` に理由を入れて返す。
+#[tauri::command]
+pub async fn refresh_book_metadata(
+    book_ids: Vec<i64>,
+    providers: ProviderSettings,
+    db: State<'_, DbConnection>,
+) -> Result<Vec<MetadataRefresh>, String> {
+    let books = {
+        let conn = db.0.lock().unwrap();
+        book_ids
+            .iter()
+            .map(|&id| active_book(&conn, id))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let mut results = Vec::new();
+    for (i, book) in books.into_iter().enumerate() {
+        let (changes, error) = match book.isbn.as_deref().and_then(normalize_isbn) {
+            None => (Vec::new(), Some("ISBN が登録されていません".to_string())),
+            Some(isbn) => {
+                if i > 0 {
+                    tokio::time::sleep(LOOKUP_INTERVAL).await;
+                }
+                match lookup_isbn(&isbn, &providers).await {
+                    Ok(info) => (metadata_changes(&book, &info), None),
+                    Err(e) => (Vec::new(), Some(e)),
+                }
+            }
+        };
+        results.push(MetadataRefresh {
+            book_id: book.id,
+            title: book.title,
+            revision: book.revision,
+            changes,
+            error,
+        });
+    }
+    Ok(results)
+}
+
+/// `refresh_book_metadata` の結果のうち、採用した項目だけを書き込んで更新後の本を返す。
+/// 取り直した後に他で更新された本が1冊でもあれば、何も書き込まずに `conflict` エラーを返す。
+#[tauri::command]
+pub fn apply_book_metadata(
+    updates: Vec<AcceptedMetadata>,
+    db: State<DbConnection>,
+) -> Result<Vec<Book>, CommandError> {
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction()?;
+    for update in &updates {
+        let current = check_revision(&tx, update.book_id, update.revision)?;
+        let mut book = to_update_book(current);
+        for (&field, value) in &update.fields {
+            set_field(&mut book, field, value)?;
+        }
+        if book.title.trim().is_empty() {
+            return Err(CommandError::Invalid("タイトル必須".into()));
+        }
+        write_book(&tx, &book)?;
+    }
+    tx.commit()?;
+    updates
+        .iter()
+        .map(|update| get_book(&conn, update.book_id).map_err(CommandError::Database))
+        .collect()
+}
+
+/// ISBN のある本のうち、書名の読み・著者・著者の読み・出版社のどれかが空欄の本を
+/// バックグラウンドで取り直し、空欄だけを埋める。既に入っている値は変えない。
+/// 1冊ごとに `metadata-fill-progress`、終わると `metadata-fill-finished` を送る。
+/// 対象の冊数を返す。
+#[tauri::command]
+pub fn start_metadata_fill(
+    providers: ProviderSettings,
+    app: AppHandle,
+    db: State<DbConnection>,
+) -> Result<usize, CommandError> {
+    if FILL_RUNNING.swap(true, Ordering::SeqCst) {
+        return Err(CommandError::Conflict("書誌情報の補完は既に実行中です".into()));
+    }
+    FILL_CANCELLED.store(false, Ordering::SeqCst);
+    let targets = match books_with_blanks(&db.0.lock().unwrap()) {
+        Ok(targets) => targets,
+        Err(e) => {
+            FILL_RUNNING.store(false, Ordering::SeqCst);
+            return Err(e.into());
+        }
+    };
+    let total = targets.len();
+    tauri::async_runtime::spawn(run_metadata_fill(app, providers, targets));
+    Ok(total)
+}
+
+/// 実行中の空欄の補完を、今の1冊が終わったところで止める。
+#[tauri::command]
+pub fn cancel_metadata_fill() {
+    FILL_CANCELLED.store(true, Ordering::SeqCst);
+}
+
+async fn run_metadata_fill(app: AppHandle, providers: ProviderSettings, targets: Vec<(i64, String, String)>) {
+    let mut summary = MetadataFillSummary {
+        total: targets.len(),
+        processed: 0,
+        updated: 0,
+        failed: 0,
+        cancelled: false,
+    };
+    for (book_id, title, isbn) in targets {
+        if FILL_CANCELLED.load(Ordering::SeqCst) {
+            summary.cancelled = true;
+            break;
+        }
+        if summary.processed > 0 {
+            tokio::time::sleep(LOOKUP_INTERVAL).await;
+        }
+        let result = match lookup_isbn(&isbn, &providers).await {
+            Ok(info) => fill_blanks(&app, book_id, &info),
+            Err(e) => Err(e),
+        };
+        summary.processed += 1;
+        let (filled, error) = match result {
+            Ok(filled) => (filled, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        if error.is_some() {
+            summary.failed += 1;
+        } else if !filled.is_empty() {
+            summary.updated += 1;
+        }
+        let _ = app.emit(
+            FILL_PROGRESS_EVENT,
+            MetadataFillProgress {
+                processed: summary.processed,
+                total: summary.total,
+                book_id,
+                title,
+                filled,
+                error,
+            },
+        );
+    }
+    FILL_RUNNING.store(false, Ordering::SeqCst);
+    let _ = app.emit(FILL_FINISHED_EVENT, summary);
+}
+
+/// 取得した値で本の空欄を埋め、埋めた項目を返す。問い合わせの間に入力された値は上書きしない。
+fn fill_blanks(app: &AppHandle, book_id: i64, info: &BookInfoFromApi) -> Result<Vec<BookField>, String> {
+    let db = app.state::<DbConnection>();
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let current = active_book(&tx, book_id)?;
+    let mut book = to_update_book(current.clone());
+    let mut filled = Vec::new();
+    for &field in REFRESH_FIELDS {
+        if current_value(&current, field).is_some() {
+            continue;
+        }
+        if let Some(value) = fetched_value(info, field) {
+            set_field(&mut book, field, value).map_err(|e| e.to_string())?;
+            filled.push(field);
+        }
+    }
+    if !filled.is_empty() {
+        write_book(&tx, &book).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+    }
+    Ok(filled)
+}
+
+/// 空欄の補完の対象（id・書名・13桁の ISBN）
+fn books_with_blanks(conn: &Connection) -> Result<Vec<(i64, String, String)>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT id, title, isbn FROM books
+         WHERE deleted_at IS NULL AND isbn IS NOT NULL
+           AND (title_kana IS NULL OR author IS NULL OR author_kana IS NULL
+                OR publisher IS NULL OR trim(publisher) = '')
+         ORDER BY id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+    })?;
+    let mut targets = Vec::new();
+    for row in rows {
+        let (id, title, isbn) = row?;
+        if let Some(isbn) = normalize_isbn(&isbn) {
+            targets.push((id, title, isbn));
+        }
+    }
+    Ok(targets)
+}
+
+/// ISBN で書誌情報を引く。`providers.priority` の順に問い合わせて空欄を後の提供元で補い、
+/// 書名・著者・出版社が揃ったところで止める。
+pub(crate) async fn lookup_isbn(isbn: &str, providers: &ProviderSettings) -> Result<BookInfoFromApi, String> {
+    let priority = if providers.priority.is_empty() {
+        DEFAULT_PRIORITY
+    } else {
+        &providers.priority
+    };
+    let mut merged: Option<BookInfoFromApi> = None;
+    let mut errors = Vec::new();
+    for &provider in priority {
+        let result = match provider {
+            MetadataProvider::Ndl => fetch_book_info_from_ndl(isbn.to_string()).await,
+            MetadataProvider::Google => {
+                fetch_book_info_from_google_books(isbn.to_string(), providers.google_api_key.clone()).await
+            }
+            MetadataProvider::Rakuten => {
+                let Some(application_id) = providers
+                    .rakuten_application_id
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|id| !id.is_empty())
+                else {
+                    continue;
+                };
+                fetch_book_info_from_rakuten(isbn.to_string(), application_id.to_string()).await
+            }
+        };
+        match result {
+            Ok(info) => {
+                let info = match merged {
+                    Some(current) => merge_book_info(current, info),
+                    None => info,
+                };
+                let complete = [BookField::Title, BookField::Author, BookField::Publisher]
+                    .iter()
+                    .all(|&field| fetched_value(&info, field).is_some());
+                merged = Some(info);
+                if complete {
+                    break;
+                }
+            }
+            Err(e) => errors.push(format!("[{}] {}", provider_label(provider), e)),
+        }
+    }
+    match merged {
+        Some(info) if fetched_value(&info, BookField::Title).is_some() => Ok(info),
+        _ if errors.is_empty() => Err("書籍情報が見つかりませんでした".to_string()),
+        _ => Err(format!("書籍情報の取得に失敗しました: {}", errors.join(" / "))),
+    }
+}
+
+/// `current` の空欄を `incoming` の値で埋める。
+fn merge_book_info(current: BookInfoFromApi, incoming: BookInfoFromApi) -> BookInfoFromApi {
+    fn pick(current: String, incoming: String) -> String {
+        if current.trim().is_empty() {
+            incoming
+        } else {
+            current
+        }
+    }
+    fn pick_opt<T>(current: Option<T>, incoming: Option<T>) -> Option<T> {
+        current.or(incoming)
+    }
+    BookInfoFromApi {
+        title: pick(current.title, incoming.title),
+        title_kana: pick_opt(current.title_kana.filter(|k| !k.trim().is_empty()), incoming.title_kana),
+        author: pick(current.author, incoming.author),
+        author_kana: pick_opt(current.author_kana.filter(|k| !k.trim().is_empty()), incoming.author_kana),
+        publisher: pick(current.publisher, incoming.publisher),
+        series: pick_opt(current.series, incoming.series),
+        volume: pick_opt(current.volume, incoming.volume),
+        isbn: pick_opt(current.isbn, incoming.isbn),
+        cover_url: pick_opt(current.cover_url, incoming.cover_url),
+    }
+}
+
+fn provider_label(provider: MetadataProvider) -> &'static str {
+    match provider {
+        MetadataProvider::Ndl => "NDL",
+        MetadataProvider::Google => "Google Books",
+        MetadataProvider::Rakuten => "楽天ブックス",
+    }
+}
+
+/// 取得した値が登録内容と異なる項目。取得できなかった項目は挙げない。
+fn metadata_changes(book: &Book, info: &BookInfoFromApi) -> Vec<MetadataChange> {
+    REFRESH_FIELDS
+        .iter()
+        .filter_map(|&field| {
+            let fetched = fetched_value(info, field)?;
+            let current = current_value(book, field);
+            (current != Some(fetched)).then(|| MetadataChange {
+                field,
+                current: current.map(str::to_string),
+                fetched: fetched.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn current_value(book: &Book, field: BookField) -> Option<&str> {
+    let value = match field {
+        BookField::Title => Some(book.title.as_str()),
+        BookField::TitleKana => book.title_kana.as_deref(),
+        BookField::Author => book.author.as_deref(),
+        BookField::AuthorKana => book.author_kana.as_deref(),
+        BookField::Publisher => book.publisher.as_deref(),
+        _ => None,
+    };
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn fetched_value(info: &BookInfoFromApi, field: BookField) -> Option<&str> {
+    let value = match field {
+        BookField::Title => Some(info.title.as_str()),
+        BookField::TitleKana => info.title_kana.as_deref(),
+        BookField::Author => Some(info.author.as_str()),
+        BookField::AuthorKana => info.author_kana.as_deref(),
+        BookField::Publisher => Some(info.publisher.as_str()),
+        _ => None,
+    };
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// 取り直しの対象の項目に値を入れる。空文字列なら値を外す。
+fn set_field(book: &mut UpdateBook, field: BookField, value: &str) -> Result<(), CommandError> {
+    let value = Some(value.trim().to_string()).filter(|v| !v.is_empty());
+    match field {
+        BookField::Title => book.title = value.unwrap_or_default(),
+        BookField::TitleKana => book.title_kana = value,
+        BookField::Author => book.author = value,
+        BookField::AuthorKana => book.author_kana = value,
+        BookField::Publisher => book.publisher = value,
+        _ => {
+            return Err(CommandError::Invalid(
+                "書誌情報の取り直しでは書名・著者・出版社とその読みだけを書き換えられます".into(),
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn to_update_book(book: Book) -> UpdateBook {
+    UpdateBook {
+        id: book.id,
+        isbn: book.isbn,
+        title: book.title,
+        title_kana: book.title_kana,
+        author: book.author,
+        author_kana: book.author_kana,
+        publisher: book.publisher,
+        price: book.price,
+        c_code: book.c_code,
+        is_read: book.is_read,
+        genre_id: book.genre_id,
+        revision: None,
+    }
+}
+
+/// ゴミ箱に無い本
+fn active_book(conn: &Connection, id: i64) -> Result<Book, String> {
+    query_books(conn, &["id = ?1".to_string()], &[&id], None)?
+        .pop()
+        .ok_or_else(|| format!("Book with id {} not found", id))
+}
@@ -1,11 +1,43 @@
+pub mod archive;
+pub mod backup;
 pub mod book;
+pub mod copy;
+pub mod cover;
+pub mod duplicate;
 pub mod genre;
 pub mod google_books_api;
+pub mod history;
+pub mod loan;
+pub mod location;
+pub mod metadata;
 pub mod ndl_api;
+pub mod note;
 pub mod rakuten_books_api;
+pub mod reading;
+pub mod series;
+pub mod stats;
+pub mod tag;
+pub mod trash;
+pub mod wishlist;
 
+pub use archive::*;
+pub use backup::*;
 pub use book::*;
+pub use copy::*;
+pub use cover::*;
+pub use duplicate::*;
 pub use genre::*;
 pub use google_books_api::*;
+pub use history::*;
+pub use loan::*;
+pub use location::*;
+pub use metadata::*;
 pub use ndl_api::*;
+pub use note::*;
 pub use rakuten_books_api::*;
+pub use reading::*;
+pub use series::*;
+pub use stats::*;
+pub use tag::*;
+pub use trash::*;
+pub use wishlist::*;
@@ -1,12 +1,24 @@
 use crate::models::BookInfoFromApi;
+use crate::normalize::{
+    normalize_author, normalize_author_reading, normalize_isbn, normalize_title_reading,
+    resolve_series,
+};
 use quick_xml::events::Event;
 use quick_xml::Reader;
 
+const NDL_SRU_URL: &str = "https://ndlsearch.ndl.go.jp/api/sru";
+const NDL_THUMBNAIL_URL: &str = "https://ndlsearch.ndl.go.jp/thumbnail";
+
+/// NDL サーチの書影。書影が無い ISBN は 404 になる。
+pub(crate) fn ndl_thumbnail_url(isbn13: &str) -> String {
+    format!("{}/{}.jpg", NDL_THUMBNAIL_URL, isbn13)
+}
+
 #[tauri::command]
 pub async fn fetch_book_info_from_ndl(isbn: String) -> Result<BookInfoFromApi, String> {
     let url = format!(
-        "https://ndlsearch.ndl.go.jp/api/sru?operation=searchRetrieve&version=1.2&recordSchema=dcndl&query=isbn={}",
-        isbn
+        "{}?operation=searchRetrieve&version=1.2&recordSchema=dcndl&query=isbn={}",
+        NDL_SRU_URL, isbn
     );
 
     let xml = reqwest::get(&url)
@@ -16,22 +28,62 @@ pub async fn fetch_book_info_from_ndl(isbn: String) -> Result<BookInfoFromApi, S
         .await
         .map_err(|e| e.to_string())?;
 
-    let mut reader = Reader::from_str(&xml);
+    // 1. <recordData> の中身をテキストとして取得
+    if let Some(escaped_xml) = record_data(&xml)?.into_iter().next() {
+        // 2. 取得したテキストを再度XMLとしてパース
+        parse_record(&escaped_xml)?.into_book_info()
+    } else {
+        Err("recordDataが見つかりませんでした。".to_string())
+    }
+}
+
+/// 書名で SRU 検索する。書名が取れたレコードだけを返す（著者・出版社は欠けていてもよい）。
+pub(crate) async fn search_ndl_by_title(title: &str) -> Result<Vec<BookInfoFromApi>, String> {
+    let query = format!("title=\"{}\"", title.replace('"', "\\\""));
+    let xml = reqwest::Client::new()
+        .get(NDL_SRU_URL)
+        .query(&[
+            ("operation", "searchRetrieve"),
+            ("version", "1.2"),
+            ("recordSchema", "dcndl"),
+            ("maximumRecords", "200"),
+            ("query", query.as_str()),
+        ])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut books = Vec::new();
+    for escaped_xml in record_data(&xml)? {
+        let record = parse_record(&escaped_xml)?;
+        if record.title.is_some() {
+            books.push(record.build());
+        }
+    }
+    Ok(books)
+}
+
+/// SRU レスポンスからすべての <recordData> の中身を取り出す。
+fn record_data(xml: &str) -> Result<Vec<String>, String> {
+    let mut reader = Reader::from_str(xml);
     reader.trim_text(true);
     let mut buf = Vec::new();
-    let mut record_data_content = None;
+    let mut records = Vec::new();
     let mut in_record_data = false;
 
-    // 1. <recordData> の中身をテキストとして取得
     loop {
         match reader.read_event_into(&mut buf) {
             Ok(Event::Start(e)) if e.name().as_ref() == b"recordData" => {
                 in_record_data = true;
             }
+            Ok(Event::End(e)) if e.name().as_ref() == b"recordData" => {
+                in_record_data = false;
+            }
             Ok(Event::Text(e)) if in_record_data => {
-                record_data_content = Some(e.unescape().unwrap().into_owned());
-                // recordDataを見つけたら、外側のループは抜けてOK
-                break;
+                records.push(e.unescape().unwrap().into_owned());
             }
             Ok(Event::Eof) => break,
             Err(e) => return Err(format!("Outer XML parsing error: {}", e)),
@@ -39,85 +91,154 @@ pub async fn fetch_book_info_from_ndl(isbn: String) -> Result<BookInfoFromApi, S
         }
         buf.clear();
     }
+    Ok(records)
+}
 
-    if let Some(escaped_xml) = record_data_content {
-        // 2. 取得したテキストを再度XMLとしてパース
-        let mut inner_reader = Reader::from_str(&escaped_xml);
-        inner_reader.trim_text(true);
-        let mut inner_buf = Vec::new();
-        let mut title = None;
-        let mut creator = None;
-        let mut publisher = None;
+/// DC-NDL の1レコードから取り出した項目
+#[derive(Default)]
+struct NdlRecord {
+    title: Option<String>,
+    title_kana: Option<String>,
+    creator: Option<String>,
+    creator_kana: Option<String>,
+    publisher: Option<String>,
+    series_title: Option<String>,
+    volume: Option<String>,
+    isbn: Option<String>,
+}
 
-        let mut in_title = false;
-        let mut in_foaf_name = false; // foaf:nameタグの中にいるか
-        // コンテキストを追跡するフラグ
-        let mut in_creator_context = false;
-        let mut in_publisher_context = false;
-
-        loop {
-            match inner_reader.read_event_into(&mut inner_buf) {
-                Ok(Event::Start(e)) => match e.name().as_ref() {
-                    b"dcterms:title" => in_title = true,
-                    b"dcterms:creator" => in_creator_context = true,
-                    b"dcterms:publisher" => in_publisher_context = true,
-                    b"foaf:name" => in_foaf_name = true,
-                    _ => {}
-                },
-                Ok(Event::End(e)) => match e.name().as_ref() {
-                    b"dcterms:creator" => in_creator_context = false,
-                    b"dcterms:publisher" => in_publisher_context = false,
-                    b"foaf:name" => in_foaf_name = false,
-                    _ => {}
-                },
-                Ok(Event::Text(e)) => {
-                    if in_title {
-                        if title.is_none() {
-                            title = Some(e.unescape().unwrap().into_owned());
-                        }
-                        in_title = false;
-                    } else if in_foaf_name {
-                        if in_creator_context {
-                            if creator.is_none() {
-                                creator = Some(e.unescape().unwrap().into_owned());
-                            }
-                        } else if in_publisher_context {
-                            if publisher.is_none() {
-                                publisher = Some(e.unescape().unwrap().into_owned());
-                            }
-                        }
-                    }
-                }
-                Ok(Event::Eof) => break,
-                Err(e) => return Err(format!("Inner XML parsing error: {}", e)),
-                _ => {}
-            }
-            inner_buf.clear();
+impl NdlRecord {
+    /// 書名・著者・出版社が揃っていれば BookInfoFromApi に変換する。
+    fn into_book_info(self) -> Result<BookInfoFromApi, String> {
+        let mut missing_items = Vec::new();
+        if self.title.is_none() {
+            missing_items.push("title");
         }
-
-        if title.is_some() && creator.is_some() && publisher.is_some() {
-            Ok(BookInfoFromApi {
-                title: title.unwrap(),
-                author: creator.unwrap(),
-                publisher: publisher.unwrap(),
-            })
+        if self.creator.is_none() {
+            missing_items.push("creator");
+        }
+        if self.publisher.is_none() {
+            missing_items.push("publisher");
+        }
+        if missing_items.is_empty() {
+            Ok(self.build())
         } else {
-            let mut missing_items = Vec::new();
-            if title.is_none() {
-                missing_items.push("title");
-            }
-            if creator.is_none() {
-                missing_items.push("creator");
-            }
-            if publisher.is_none() {
-                missing_items.push("publisher");
-            }
             Err(format!(
                 "書籍情報が見つかりませんでした。不足: [{}]",
                 missing_items.join(", ")
             ))
         }
-    } else {
-        Err("recordDataが見つかりませんでした。".to_string())
     }
-}
\ No newline at end of file
+
+    fn build(self) -> BookInfoFromApi {
+        let title = self.title.unwrap_or_default();
+        let isbn = self.isbn.as_deref().and_then(normalize_isbn);
+        let (series, volume) = resolve_series(
+            &title,
+            self.series_title.as_deref(),
+            self.volume.as_deref(),
+        );
+        BookInfoFromApi {
+            title,
+            title_kana: self
+                .title_kana
+                .map(|kana| normalize_title_reading(&kana))
+                .filter(|kana| !kana.is_empty()),
+            author: self
+                .creator
+                .map(|creator| normalize_author(&creator))
+                .unwrap_or_default(),
+            author_kana: self
+                .creator_kana
+                .map(|kana| normalize_author_reading(&kana))
+                .filter(|kana| !kana.is_empty()),
+            publisher: self.publisher.unwrap_or_default(),
+            series,
+            volume,
+            cover_url: isbn.as_deref().map(ndl_thumbnail_url),
+            isbn,
+        }
+    }
+}
+
+fn parse_record(escaped_xml: &str) -> Result<NdlRecord, String> {
+    let mut inner_reader = Reader::from_str(escaped_xml);
+    inner_reader.trim_text(true);
+    let mut inner_buf = Vec::new();
+    let mut record = NdlRecord::default();
+
+    let mut in_title = false;
+    let mut in_title_context = false; // dc:title（読みを含む）の中にいるか
+    let mut in_foaf_name = false; // foaf:nameタグの中にいるか
+    let mut in_transcription = false; // dcndl:transcription（読み）の中にいるか
+    let mut in_isbn = false; // rdf:datatype が ISBN の dcterms:identifier の中にいるか
+    // コンテキストを追跡するフラグ
+    let mut in_creator_context = false;
+    let mut in_publisher_context = false;
+    let mut in_series_context = false;
+    let mut in_volume_context = false;
+
+    loop {
+        match inner_reader.read_event_into(&mut inner_buf) {
+            Ok(Event::Start(e)) => match e.name().as_ref() {
+                b"dcterms:title" => in_title = true,
+                b"dc:title" => in_title_context = true,
+                b"dcterms:creator" => in_creator_context = true,
+                b"dcterms:publisher" => in_publisher_context = true,
+                b"dcndl:seriesTitle" => in_series_context = true,
+                b"dcndl:volume" => in_volume_context = true,
+                b"foaf:name" => in_foaf_name = true,
+                b"dcndl:transcription" => in_transcription = true,
+                b"dcterms:identifier" => {
+                    in_isbn = e.attributes().flatten().any(|a| {
+                        a.key.as_ref() == b"rdf:datatype" && a.value.ends_with(b"ISBN")
+                    })
+                }
+                _ => {}
+            },
+            Ok(Event::End(e)) => match e.name().as_ref() {
+                b"dc:title" => in_title_context = false,
+                b"dcterms:creator" => in_creator_context = false,
+                b"dcterms:publisher" => in_publisher_context = false,
+                b"dcndl:seriesTitle" => in_series_context = false,
+                b"dcndl:volume" => in_volume_context = false,
+                b"foaf:name" => in_foaf_name = false,
+                b"dcndl:transcription" => in_transcription = false,
+                b"dcterms:identifier" => in_isbn = false,
+                _ => {}
+            },
+            Ok(Event::Text(e)) => {
+                let text = e.unescape().unwrap().into_owned();
+                if in_title {
+                    if record.title.is_none() {
+                        record.title = Some(text);
+                    }
+                    in_title = false;
+                } else if in_foaf_name {
+                    if in_creator_context && record.creator.is_none() {
+                        record.creator = Some(text);
+                    } else if in_publisher_context && record.publisher.is_none() {
+                        record.publisher = Some(text);
+                    }
+                } else if in_isbn {
+                    if record.isbn.is_none() {
+                        record.isbn = Some(text);
+                    }
+                } else if in_transcription && in_creator_context && record.creator_kana.is_none() {
+                    record.creator_kana = Some(text);
+                } else if in_transcription && in_title_context && record.title_kana.is_none() {
+                    record.title_kana = Some(text);
+                } else if in_series_context && !in_transcription && record.series_title.is_none() {
+                    record.series_title = Some(text);
+                } else if in_volume_context && !in_transcription && record.volume.is_none() {
+                    record.volume = Some(text);
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Inner XML parsing error: {}", e)),
+            _ => {}
+        }
+        inner_buf.clear();
+    }
+    Ok(record)
+}
@@ -0,0 +1,251 @@
+use crate::db::DbConnection;
+use crate::history::{record_update, snapshot_book};
+use crate::models::{BookNote, BookReview, NewBookNote, NoteKind, UpdateBookNote};
+use rusqlite::{Connection, OptionalExtension};
+use tauri::State;
+
+const NOTE_COLUMNS: &str = "id, book_id, kind, body, page, created_at";
+
+#[tauri::command]
+pub fn get_book_review(book_id: i64, db: State<DbConnection>) -> Result<BookReview, String> {
+    let conn = db.0.lock().unwrap();
+    get_review(&conn, book_id)
+}
+
+/// 評価とレビューを保存する。評価は 0.5 刻みの 0.5〜5、None で未評価に戻す。
+#[tauri::command]
+pub fn set_book_review(
+    book_id: i64,
+    rating: Option<f64>,
+    review: Option<String>,
+    db: State<DbConnection>,
+) -> Result<BookReview, String> {
+    if rating.is_some_and(|r| !(0.5..=5.0).contains(&r) || (r * 2.0).fract() != 0.0) {
+        return Err("評価は0.5〜5の0.5刻みで指定してください".into());
+    }
+    let review = review.filter(|text| !text.trim().is_empty());
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let before = snapshot_book(&tx, book_id).map_err(|e| e.to_string())?;
+    let affected = tx
+        .execute(
+            "UPDATE books SET rating = ?1, review = ?2 WHERE id = ?3",
+            rusqlite::params![rating, review, book_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if affected == 0 {
+        return Err(format!("Book with id {} not found", book_id));
+    }
+    record_update(&tx, book_id, before.as_ref()).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    get_review(&conn, book_id)
+}
+
+/// 本のメモ・引用をページ順で返す。ページの無いものは最後に作成順で並ぶ。
+#[tauri::command]
+pub fn get_book_notes(book_id: i64, db: State<DbConnection>) -> Result<Vec<BookNote>, String> {
+    let conn = db.0.lock().unwrap();
+    list_notes(&conn, book_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn add_book_note(note: NewBookNote, db: State<DbConnection>) -> Result<BookNote, String> {
+    validate_note(&note.body, note.page)?;
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let book_exists: bool = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM books WHERE id = ?1 AND deleted_at IS NULL)",
+            [note.book_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !book_exists {
+        return Err(format!("Book with id {} not found", note.book_id));
+    }
+    let before = snapshot_book(&tx, note.book_id).map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO book_notes (book_id, kind, body, page) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![note.book_id, note.kind.as_str(), note.body.trim(), note.page],
+    )
+    .map_err(|e| e.to_string())?;
+    let id = tx.last_insert_rowid();
+    record_update(&tx, note.book_id, before.as_ref()).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    get_note(&conn, id)
+}
+
+#[tauri::command]
+pub fn update_book_note(note: UpdateBookNote, db: State<DbConnection>) -> Result<BookNote, String> {
+    validate_note(&note.body, note.page)?;
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let book_id = note_book_id(&tx, note.id)?;
+    let before = snapshot_book(&tx, book_id).map_err(|e| e.to_string())?;
+    tx.execute(
+        "UPDATE book_notes SET kind = ?1, body = ?2, page = ?3 WHERE id = ?4",
+        rusqlite::params![note.kind.as_str(), note.body.trim(), note.page, note.id],
+    )
+    .map_err(|e| e.to_string())?;
+    record_update(&tx, book_id, before.as_ref()).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    get_note(&conn, note.id)
+}
+
+#[tauri::command]
+pub fn delete_book_note(id: i64, db: State<DbConnection>) -> Result<(), String> {
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let book_id = note_book_id(&tx, id)?;
+    let before = snapshot_book(&tx, book_id).map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM book_notes WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    record_update(&tx, book_id, before.as_ref()).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// メモの付いている本の id
+fn note_book_id(conn: &Connection, id: i64) -> Result<i64, String> {
+    conn.query_row("SELECT book_id FROM book_notes WHERE id = ?1", [id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Note with id {} not found", id))
+}
+
+/// 本の評価・レビュー・メモ・引用を Markdown にまとめる。
+#[tauri::command]
+pub fn export_book_notes_markdown(book_id: i64, db: State<DbConnection>) -> Result<String, String> {
+    let conn = db.0.lock().unwrap();
+    let (title, author, publisher): (String, Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT title, author, publisher FROM books WHERE id = ?1",
+            [book_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Book with id {} not found", book_id))?;
+    let review = get_review(&conn, book_id)?;
+    let notes = list_notes(&conn, book_id).map_err(|e| e.to_string())?;
+
+    let mut md = format!("# {}\n\n", title);
+    let byline: Vec<&str> = [author.as_deref(), publisher.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter(|s| !s.is_empty())
+        .collect();
+    if !byline.is_empty() {
+        md.push_str(&format!("{}\n\n", byline.join(" / ")));
+    }
+    if let Some(rating) = review.rating {
+        md.push_str(&format!("評価: {} ({:.1})\n\n", stars(rating), rating));
+    }
+    if let Some(text) = &review.review {
+        md.push_str(&format!("## レビュー\n\n{}\n\n", text.trim()));
+    }
+    if !notes.is_empty() {
+        md.push_str("## メモ・引用\n\n");
+        for note in &notes {
+            let page = note.page.map(|p| format!("p.{}", p));
+            match note.kind {
+                NoteKind::Quote => {
+                    for line in note.body.lines() {
+                        md.push_str(&format!("> {}\n", line).replace("> \n", ">\n"));
+                    }
+                    if let Some(page) = page {
+                        md.push_str(&format!(">\n> — {}\n", page));
+                    }
+                }
+                NoteKind::Note => {
+                    let mut lines = note.body.lines();
+                    let first = lines.next().unwrap_or_default();
+                    match page {
+                        Some(page) => md.push_str(&format!("- ({}) {}\n", page, first)),
+                        None => md.push_str(&format!("- {}\n", first)),
+                    }
+                    for line in lines {
+                        md.push_str(&format!("  {}\n", line));
+                    }
+                }
+            }
+            md.push('\n');
+        }
+    }
+    Ok(format!("{}\n", md.trim_end()))
+}
+
+fn get_review(conn: &Connection, book_id: i64) -> Result<BookReview, String> {
+    conn.query_row(
+        "SELECT id, rating, review FROM books WHERE id = ?1",
+        [book_id],
+        |row| {
+            Ok(BookReview {
+                book_id: row.get(0)?,
+                rating: row.get(1)?,
+                review: row.get(2)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Book with id {} not found", book_id))
+}
+
+fn list_notes(conn: &Connection, book_id: i64) -> Result<Vec<BookNote>, rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM book_notes WHERE book_id = ?1 ORDER BY page IS NULL, page, id",
+        NOTE_COLUMNS
+    ))?;
+    let rows = stmt.query_map([book_id], row_to_note)?;
+    rows.collect()
+}
+
+fn get_note(conn: &Connection, id: i64) -> Result<BookNote, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM book_notes WHERE id = ?1", NOTE_COLUMNS),
+        [id],
+        row_to_note,
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn validate_note(body: &str, page: Option<i64>) -> Result<(), String> {
+    if body.trim().is_empty() {
+        return Err("メモが空です".into());
+    }
+    if page.is_some_and(|p| p <= 0) {
+        return Err("ページ数が不正です".into());
+    }
+    Ok(())
+}
+
+/// 3.5 → "★★★½☆"
+fn stars(rating: f64) -> String {
+    let full = rating.floor() as usize;
+    let half = rating.fract() > 0.0;
+    let empty = 5 - full - usize::from(half);
+    format!(
+        "{}{}{}",
+        "★".repeat(full),
+        if half { "½" } else { "" },
+        "☆".repeat(empty)
+    )
+}
+
+fn row_to_note(row: &rusqlite::Row) -> rusqlite::Result<BookNote> {
+    let kind: String = row.get(2)?;
+    Ok(BookNote {
+        id: row.get(0)?,
+        book_id: row.get(1)?,
+        kind: NoteKind::parse(&kind).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                2,
+                rusqlite::types::Type::Text,
+                format!("unknown note kind: {}", kind).into(),
+            )
+        })?,
+        body: row.get(3)?,
+        page: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
@@ -1,14 +1,20 @@
 use crate::models::BookInfoFromApi;
+use crate::normalize::{
+    normalize_author, normalize_author_reading, normalize_isbn, normalize_title_reading,
+    resolve_series,
+};
 use serde_json::Value;
 
+const RAKUTEN_BOOKS_URL: &str = "https://app.rakuten.co.jp/services/api/BooksBook/Search/20170404";
+
 #[tauri::command]
 pub async fn fetch_book_info_from_rakuten(
     isbn: String,
     application_id: String,
 ) -> Result<BookInfoFromApi, String> {
     let url = format!(
-        "https://app.rakuten.co.jp/services/api/BooksBook/Search/20170404?applicationId={}&isbn={}",
-        application_id, isbn
+        "{}?applicationId={}&isbn={}",
+        RAKUTEN_BOOKS_URL, application_id, isbn
     );
 
     let resp = reqwest::get(&url).await.map_err(|e| e.to_string())?;
@@ -19,27 +25,12 @@ pub async fn fetch_book_info_from_rakuten(
             .first()
             .and_then(|i| i.get("Item"))
         {
-            let title = item
-                .get("title")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-            let author = item
-                .get("author")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-            let publisher = item
-                .get("publisherName")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-            if !title.is_empty() && !author.is_empty() && !publisher.is_empty() {
-                return Ok(BookInfoFromApi {
-                    title,
-                    author,
-                    publisher,
-                });
+            let book_info = parse_item(item);
+            if !book_info.title.is_empty()
+                && !book_info.author.is_empty()
+                && !book_info.publisher.is_empty()
+            {
+                return Ok(book_info);
             } else {
                 return Err("書籍情報が不足しています".to_string());
             }
@@ -49,3 +40,87 @@ pub async fn fetch_book_info_from_rakuten(
     Err("書籍情報が見つかりませんでした".to_string())
 }
 
+/// 書名で検索する。書名が取れた商品だけを返す。
+pub(crate) async fn search_rakuten_by_title(
+    title: &str,
+    application_id: &str,
+) -> Result<Vec<BookInfoFromApi>, String> {
+    let resp = reqwest::Client::new()
+        .get(RAKUTEN_BOOKS_URL)
+        .query(&[
+            ("applicationId", application_id),
+            ("title", title),
+            ("hits", "30"),
+            ("sort", "-releaseDate"),
+        ])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let json: Value = resp.json().await.map_err(|e| e.to_string())?;
+
+    Ok(json["Items"]
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|i| i.get("Item"))
+                .map(parse_item)
+                .filter(|book_info| !book_info.title.is_empty())
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+fn parse_item(item: &Value) -> BookInfoFromApi {
+    let title = item
+        .get("title")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    let title_kana = item
+        .get("titleKana")
+        .and_then(|v| v.as_str())
+        .map(normalize_title_reading)
+        .filter(|kana| !kana.is_empty());
+    let author = item
+        .get("author")
+        .and_then(|v| v.as_str())
+        .map(normalize_author)
+        .unwrap_or_default();
+    let author_kana = item
+        .get("authorKana")
+        .and_then(|v| v.as_str())
+        .map(normalize_author_reading)
+        .filter(|kana| !kana.is_empty());
+    let publisher = item
+        .get("publisherName")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    let (series, volume) = resolve_series(
+        &title,
+        item.get("seriesName").and_then(|v| v.as_str()),
+        None,
+    );
+    let isbn = item
+        .get("isbn")
+        .and_then(|v| v.as_str())
+        .and_then(normalize_isbn);
+    // 書影の無い本は noimage の画像が返ってくるので使わない
+    let cover_url = item
+        .get("largeImageUrl")
+        .and_then(|v| v.as_str())
+        .filter(|url| !url.is_empty() && !url.contains("noimage"))
+        .map(str::to_string);
+    BookInfoFromApi {
+        title,
+        title_kana,
+        author,
+        author_kana,
+        publisher,
+        series,
+        volume,
+        isbn,
+        cover_url,
+    }
+}
@@ -0,0 +1,265 @@
+use crate::commands::book::{row_to_book, BOOK_COLUMNS};
+use crate::db::DbConnection;
+use crate::models::{BookReadCount, CurrentReading, ReadingSession, ReadingStatus};
+use rusqlite::{Connection, OptionalExtension};
+use tauri::State;
+
+const SESSION_COLUMNS: &str =
+    "id, book_id, status, started_at, finished_at, current_page, total_pages";
+
+/// 本を読み始める。`started_at` を省略すると今日の日付になる。
+#[tauri::command]
+pub fn start_reading(
+    book_id: i64,
+    started_at: Option<String>,
+    total_pages: Option<i64>,
+    db: State<DbConnection>,
+) -> Result<ReadingSession, String> {
+    let conn = db.0.lock().unwrap();
+    let book_exists: bool = conn
+        .query_row("SELECT EXISTS(SELECT 1 FROM books WHERE id = ?1 AND deleted_at IS NULL)", [book_id], |row| {
+            row.get(0)
+        })
+        .map_err(|e| e.to_string())?;
+    if !book_exists {
+        return Err(format!("Book with id {} not found", book_id));
+    }
+    let reading: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM reading_sessions WHERE book_id = ?1 AND status = 'reading')",
+            [book_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if reading {
+        return Err("この本は既に読書中です".into());
+    }
+    if total_pages.is_some_and(|pages| pages <= 0) {
+        return Err("ページ数が不正です".into());
+    }
+    let started_at = normalize_date(&conn, started_at.as_deref())?;
+    conn.execute(
+        "INSERT INTO reading_sessions (book_id, status, started_at, total_pages)
+         VALUES (?1, 'reading', ?2, ?3)",
+        rusqlite::params![book_id, started_at, total_pages],
+    )
+    .map_err(|e| e.to_string())?;
+    get_session(&conn, conn.last_insert_rowid())
+}
+
+/// 読んでいるページを記録する。`total_pages` を渡すと総ページ数も更新する。
+#[tauri::command]
+pub fn update_reading_progress(
+    session_id: i64,
+    current_page: i64,
+    total_pages: Option<i64>,
+    db: State<DbConnection>,
+) -> Result<ReadingSession, String> {
+    let conn = db.0.lock().unwrap();
+    let session = get_session(&conn, session_id)?;
+    let total_pages = total_pages.or(session.total_pages);
+    if current_page < 0 || total_pages.is_some_and(|total| current_page > total) {
+        return Err("ページ数が不正です".into());
+    }
+    conn.execute(
+        "UPDATE reading_sessions SET current_page = ?1, total_pages = ?2 WHERE id = ?3",
+        rusqlite::params![current_page, total_pages, session_id],
+    )
+    .map_err(|e| e.to_string())?;
+    get_session(&conn, session_id)
+}
+
+/// 読了にする。`finished_at` を省略すると今日の日付になる。
+#[tauri::command]
+pub fn finish_reading(
+    session_id: i64,
+    finished_at: Option<String>,
+    db: State<DbConnection>,
+) -> Result<ReadingSession, String> {
+    let conn = db.0.lock().unwrap();
+    end_session(&conn, session_id, ReadingStatus::Finished, finished_at.as_deref())
+}
+
+/// 途中でやめたことにする。読んだページ数はそのまま残す。
+#[tauri::command]
+pub fn abandon_reading(
+    session_id: i64,
+    finished_at: Option<String>,
+    db: State<DbConnection>,
+) -> Result<ReadingSession, String> {
+    let conn = db.0.lock().unwrap();
+    end_session(&conn, session_id, ReadingStatus::Abandoned, finished_at.as_deref())
+}
+
+#[tauri::command]
+pub fn delete_reading_session(session_id: i64, db: State<DbConnection>) -> Result<(), String> {
+    let conn = db.0.lock().unwrap();
+    let affected = conn
+        .execute("DELETE FROM reading_sessions WHERE id = ?1", [session_id])
+        .map_err(|e| e.to_string())?;
+    if affected == 0 {
+        return Err(format!("Reading session with id {} not found", session_id));
+    }
+    Ok(())
+}
+
+/// 読書中の本を、読み始めた日の新しい順で返す。
+#[tauri::command]
+pub fn get_current_reading(db: State<DbConnection>) -> Result<Vec<CurrentReading>, String> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {}, session_id, book_id, status, started_at, finished_at, current_page, total_pages
+             FROM books
+             JOIN (SELECT id AS session_id, book_id, status, started_at, finished_at, current_page, total_pages
+                   FROM reading_sessions WHERE status = 'reading') s ON s.book_id = books.id
+             WHERE books.deleted_at IS NULL
+             ORDER BY started_at DESC, session_id DESC",
+            BOOK_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(CurrentReading {
+                session: ReadingSession {
+                    id: row.get("session_id")?,
+                    book_id: row.get("book_id")?,
+                    status: parse_status(row.get_ref("status")?.as_str()?)?,
+                    started_at: row.get("started_at")?,
+                    finished_at: row.get("finished_at")?,
+                    current_page: row.get("current_page")?,
+                    total_pages: row.get("total_pages")?,
+                },
+                book: row_to_book(row)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// 本の読書記録を古い順に返す。日付の無い記録は最後に並ぶ。
+#[tauri::command]
+pub fn get_reading_sessions(
+    book_id: i64,
+    db: State<DbConnection>,
+) -> Result<Vec<ReadingSession>, String> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM reading_sessions WHERE book_id = ?1
+             ORDER BY COALESCE(started_at, finished_at) IS NULL, COALESCE(started_at, finished_at), id",
+            SESSION_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([book_id], row_to_session)
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// 読了回数。`book_ids` を省略すると一度でも読了した本すべてを返す。
+#[tauri::command]
+pub fn get_read_counts(
+    book_ids: Option<Vec<i64>>,
+    db: State<DbConnection>,
+) -> Result<Vec<BookReadCount>, String> {
+    let conn = db.0.lock().unwrap();
+    let filter = match book_ids.as_deref() {
+        Some(ids) => format!(
+            "AND book_id IN ({})",
+            ids.iter().map(i64::to_string).collect::<Vec<_>>().join(", ")
+        ),
+        None => String::new(),
+    };
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT book_id, COUNT(*) FROM reading_sessions
+             WHERE status = 'finished' {}
+             GROUP BY book_id ORDER BY book_id",
+            filter
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(BookReadCount {
+                book_id: row.get(0)?,
+                read_count: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+fn end_session(
+    conn: &Connection,
+    session_id: i64,
+    status: ReadingStatus,
+    finished_at: Option<&str>,
+) -> Result<ReadingSession, String> {
+    let session = get_session(conn, session_id)?;
+    if session.status != ReadingStatus::Reading {
+        return Err("読書中の記録ではありません".into());
+    }
+    let finished_at = normalize_date(conn, finished_at)?;
+    if session
+        .started_at
+        .as_deref()
+        .is_some_and(|started_at| finished_at.as_str() < started_at)
+    {
+        return Err("終了日が開始日より前です".into());
+    }
+    // 読了なら最後のページまで読んだことにする
+    let current_page = match status {
+        ReadingStatus::Finished => session.total_pages.or(session.current_page),
+        _ => session.current_page,
+    };
+    conn.execute(
+        "UPDATE reading_sessions SET status = ?1, finished_at = ?2, current_page = ?3 WHERE id = ?4",
+        rusqlite::params![status.as_str(), finished_at, current_page, session_id],
+    )
+    .map_err(|e| e.to_string())?;
+    get_session(conn, session_id)
+}
+
+fn get_session(conn: &Connection, session_id: i64) -> Result<ReadingSession, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM reading_sessions WHERE id = ?1", SESSION_COLUMNS),
+        [session_id],
+        row_to_session,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Reading session with id {} not found", session_id))
+}
+
+/// "YYYY-MM-DD" に揃える。省略時は今日（ローカル時刻）。
+pub(crate) fn normalize_date(conn: &Connection, date: Option<&str>) -> Result<String, String> {
+    let normalized: Option<String> = match date {
+        Some(date) => conn.query_row("SELECT date(?1)", [date.trim()], |row| row.get(0)),
+        None => conn.query_row("SELECT date('now', 'localtime')", [], |row| row.get(0)),
+    }
+    .map_err(|e| e.to_string())?;
+    normalized.ok_or_else(|| format!("日付の形式が不正です: {}", date.unwrap_or_default()))
+}
+
+fn row_to_session(row: &rusqlite::Row) -> rusqlite::Result<ReadingSession> {
+    Ok(ReadingSession {
+        id: row.get(0)?,
+        book_id: row.get(1)?,
+        status: parse_status(row.get_ref(2)?.as_str()?)?,
+        started_at: row.get(3)?,
+        finished_at: row.get(4)?,
+        current_page: row.get(5)?,
+        total_pages: row.get(6)?,
+    })
+}
+
+fn parse_status(s: &str) -> rusqlite::Result<ReadingStatus> {
+    ReadingStatus::parse(s).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            2,
+            rusqlite::types::Type::Text,
+            format!("unknown reading status: {}", s).into(),
+        )
+    })
+}
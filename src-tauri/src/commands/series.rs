@@ -0,0 +1,214 @@
+use super::book::{row_to_book, BOOK_COLUMNS};
+use super::ndl_api::search_ndl_by_title;
+use super::rakuten_books_api::search_rakuten_by_title;
+use crate::db::DbConnection;
+use crate::error::CommandError;
+use crate::history::{record_update, snapshot_book};
+use crate::models::{BookInfoFromApi, SeriesSummary, SeriesVolume, VolumeCandidate};
+use crate::normalize::{normalize_isbn, unify_width};
+use std::collections::HashSet;
+use tauri::State;
+
+#[tauri::command]
+pub fn get_series_list(db: State<DbConnection>) -> Result<Vec<SeriesSummary>, String> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn
+        .prepare(
+            "SELECT s.id, s.title, s.title_kana, COUNT(bs.book_id), GROUP_CONCAT(bs.volume)
+             FROM series s
+             JOIN book_series bs ON bs.series_id = s.id
+             JOIN books b ON b.id = bs.book_id AND b.deleted_at IS NULL
+             GROUP BY s.id
+             ORDER BY COALESCE(s.title_kana, s.title) COLLATE KANA",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            let volumes: Option<String> = row.get(4)?;
+            let owned_volumes = parse_volume_list(volumes.as_deref());
+            Ok(SeriesSummary {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                title_kana: row.get(2)?,
+                book_count: row.get(3)?,
+                missing_volumes: missing_volumes(&owned_volumes),
+                owned_volumes,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_series_books(series_id: i64, db: State<DbConnection>) -> Result<Vec<SeriesVolume>, String> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {}, volume FROM books
+             JOIN book_series ON book_series.book_id = books.id
+             WHERE series_id = ?1 AND books.deleted_at IS NULL
+             ORDER BY volume IS NULL, volume, COALESCE(title_kana, title) COLLATE KANA",
+            BOOK_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([series_id], |row| {
+            Ok(SeriesVolume {
+                volume: row.get("volume")?,
+                book: row_to_book(row)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// 巻数として受け付ける最大値
+pub const MAX_VOLUME: i64 = 9999;
+
+/// 巻数が 1〜`MAX_VOLUME` か確かめる。
+pub(crate) fn validate_volume(volume: Option<i64>) -> Result<(), String> {
+    match volume {
+        Some(volume) if !(1..=MAX_VOLUME).contains(&volume) => {
+            Err(format!("巻数は1〜{}で指定してください", MAX_VOLUME))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 本のシリーズと巻数を設定する。シリーズ名が空ならシリーズから外す。
+/// ゴミ箱の本や存在しない本なら `not_found` エラーを返す。
+#[tauri::command]
+pub fn set_book_series(
+    book_id: i64,
+    series: Option<String>,
+    volume: Option<i64>,
+    db: State<DbConnection>,
+) -> Result<(), CommandError> {
+    validate_volume(volume).map_err(CommandError::Invalid)?;
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction()?;
+    let active: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM books WHERE id = ?1 AND deleted_at IS NULL)",
+        [book_id],
+        |row| row.get(0),
+    )?;
+    if !active {
+        return Err(CommandError::NotFound(format!("Book with id {} not found", book_id)));
+    }
+    let before = snapshot_book(&tx, book_id)?;
+    let series_id = match series.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(title) => Some(crate::db::ensure_series(&tx, title)?),
+        None => None,
+    };
+    crate::db::set_book_series(&tx, book_id, series_id, volume)?;
+    record_update(&tx, book_id, before.as_ref())?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// シリーズ名で NDL（と楽天ブックス）を検索し、まだ所持していない巻を返す。
+/// 所持済みの判定は ISBN と巻数の両方で行う。楽天はアプリケーションIDがあるときだけ検索する。
+#[tauri::command]
+pub async fn find_missing_volumes(
+    series_id: i64,
+    application_id: Option<String>,
+    db: State<'_, DbConnection>,
+) -> Result<Vec<VolumeCandidate>, String> {
+    let (series_title, owned_volumes, owned_isbns) = {
+        let conn = db.0.lock().unwrap();
+        let series_title: String = conn
+            .query_row("SELECT title FROM series WHERE id = ?1", [series_id], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT volume FROM book_series
+                 JOIN books ON books.id = book_series.book_id AND books.deleted_at IS NULL
+                 WHERE series_id = ?1 AND volume IS NOT NULL",
+            )
+            .map_err(|e| e.to_string())?;
+        let mut owned_volumes = stmt
+            .query_map([series_id], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        owned_volumes.sort_unstable();
+        owned_volumes.dedup();
+        let mut stmt = conn
+            .prepare("SELECT isbn FROM books WHERE isbn IS NOT NULL AND deleted_at IS NULL")
+            .map_err(|e| e.to_string())?;
+        let owned_isbns = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?
+            .filter_map(|isbn| isbn.ok().as_deref().and_then(normalize_isbn))
+            .collect::<HashSet<_>>();
+        (series_title, owned_volumes, owned_isbns)
+    };
+
+    let mut found: Vec<(BookInfoFromApi, &str)> = search_ndl_by_title(&series_title)
+        .await?
+        .into_iter()
+        .map(|book_info| (book_info, "ndl"))
+        .collect();
+    if let Some(application_id) = application_id.as_deref().map(str::trim).filter(|id| !id.is_empty()) {
+        found.extend(
+            search_rakuten_by_title(&series_title, application_id)
+                .await?
+                .into_iter()
+                .map(|book_info| (book_info, "rakuten")),
+        );
+    }
+
+    let latest_owned = owned_volumes.last().copied().unwrap_or(0);
+    let mut seen_isbns = HashSet::new();
+    let mut seen_volumes = HashSet::new();
+    let mut candidates = Vec::new();
+    for (book_info, source) in found {
+        // 巻数の分からない本（ガイドブックなど）は位置付けられないので除く
+        let (Some(isbn), Some(volume)) = (book_info.isbn.clone(), book_info.volume) else {
+            continue;
+        };
+        let same_series = book_info
+            .series
+            .as_deref()
+            .is_some_and(|series| unify_width(series) == series_title);
+        if !same_series || owned_isbns.contains(&isbn) || !seen_isbns.insert(isbn.clone()) {
+            continue;
+        }
+        // 同じ巻の別版（新装版など）や、既に候補に挙げた巻は除く
+        if owned_volumes.binary_search(&volume).is_ok() || !seen_volumes.insert(volume) {
+            continue;
+        }
+        candidates.push(VolumeCandidate {
+            isbn,
+            title: book_info.title,
+            author: book_info.author,
+            publisher: book_info.publisher,
+            volume,
+            is_newer: volume > latest_owned,
+            source: source.to_string(),
+        });
+    }
+    candidates.sort_by_key(|c| c.volume);
+    Ok(candidates)
+}
+
+fn parse_volume_list(volumes: Option<&str>) -> Vec<i64> {
+    let mut owned: Vec<i64> = volumes
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|v| v.trim().parse().ok())
+        .collect();
+    owned.sort_unstable();
+    owned.dedup();
+    owned
+}
+
+fn missing_volumes(owned: &[i64]) -> Vec<i64> {
+    match owned.last() {
+        // 上限を入れる前に保存された巻数が大きすぎても、欠巻の一覧が膨らまないようにする
+        Some(&last) => (1..last.min(MAX_VOLUME))
+            .filter(|v| owned.binary_search(v).is_err())
+            .collect(),
+        None => Vec::new(),
+    }
+}
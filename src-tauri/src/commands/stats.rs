@@ -0,0 +1,318 @@
+use crate::collation::compare_kana;
+use crate::commands::copy::acquisition_kind;
+use crate::commands::reading::normalize_date;
+use crate::db::DbConnection;
+use crate::models::{
+    AcquisitionKind, AcquisitionSpending, PeriodSpending, PeriodStat, RankedCount, ReadingStats,
+    SpendingSummary,
+};
+use crate::normalize::AUTHOR_SEPARATOR;
+use rusqlite::Connection;
+use std::collections::{BTreeMap, HashMap};
+use tauri::State;
+
+/// 著者・出版社・ジャンルのランキングに含める件数
+const TOP_N: usize = 10;
+
+/// 期間中に終わった読書記録
+struct EndedSession {
+    finished_at: String,
+    finished: bool,
+    pages: i64,
+    days: Option<f64>,
+    author: Option<String>,
+    publisher: Option<String>,
+    genre: Option<String>,
+}
+
+/// `from`〜`to`（"YYYY-MM-DD"、両端を含む）の読書統計を返す。省略した側は記録のある範囲まで。
+/// ページ数は読了した記録の総ページ数と、途中でやめた記録の読んだところまでを合計する。
+#[tauri::command]
+pub fn get_reading_stats(
+    from: Option<String>,
+    to: Option<String>,
+    db: State<DbConnection>,
+) -> Result<ReadingStats, String> {
+    let conn = db.0.lock().unwrap();
+    let (from, to) = normalize_period(&conn, from, to)?;
+
+    let sessions = ended_sessions(&conn, from.as_deref(), to.as_deref()).map_err(|e| e.to_string())?;
+    let finished: Vec<&EndedSession> = sessions.iter().filter(|s| s.finished).collect();
+
+    // 月別・年別。期間の端が指定されていなければ記録のある最初と最後の月まで。
+    let first_month = from
+        .as_deref()
+        .or(sessions.first().map(|s| s.finished_at.as_str()))
+        .map(|date| date[..7].to_string());
+    let last_month = to
+        .as_deref()
+        .or(sessions.last().map(|s| s.finished_at.as_str()))
+        .map(|date| date[..7].to_string());
+    let mut months: BTreeMap<String, (i64, i64)> = BTreeMap::new();
+    if let (Some(first), Some(last)) = (&first_month, &last_month) {
+        for month in month_range(first, last) {
+            months.insert(month, (0, 0));
+        }
+    }
+    for session in &sessions {
+        let entry = months.entry(session.finished_at[..7].to_string()).or_default();
+        entry.0 += i64::from(session.finished);
+        entry.1 += session.pages;
+    }
+    let mut years: BTreeMap<String, (i64, i64)> = BTreeMap::new();
+    for (month, (count, pages)) in &months {
+        let entry = years.entry(month[..4].to_string()).or_default();
+        entry.0 += count;
+        entry.1 += pages;
+    }
+
+    let day_counts: Vec<f64> = finished.iter().filter_map(|s| s.days).collect();
+    let average_days_to_finish = if day_counts.is_empty() {
+        None
+    } else {
+        Some(day_counts.iter().sum::<f64>() / day_counts.len() as f64)
+    };
+
+    let (backlog_count, backlog_price): (i64, i64) = conn
+        .query_row(
+            "SELECT COUNT(*), COALESCE(SUM(price), 0) FROM books
+             WHERE is_read = 0 AND deleted_at IS NULL",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+
+    Ok(ReadingStats {
+        from,
+        to,
+        finished_count: finished.len() as i64,
+        pages_read: sessions.iter().map(|s| s.pages).sum(),
+        by_month: period_stats(months),
+        by_year: period_stats(years),
+        top_authors: ranking(finished.iter().flat_map(|s| {
+            s.author
+                .as_deref()
+                .into_iter()
+                .flat_map(|author| author.split(AUTHOR_SEPARATOR))
+        })),
+        top_publishers: ranking(finished.iter().filter_map(|s| s.publisher.as_deref())),
+        top_genres: ranking(finished.iter().filter_map(|s| s.genre.as_deref())),
+        average_days_to_finish,
+        backlog_count,
+        backlog_price,
+    })
+}
+
+/// `from`〜`to`（"YYYY-MM-DD"、両端を含む）に入手した冊の支出を集計する。
+/// 入手日の無い冊は集計せず、購入価格の無い冊は 0 円として数える。
+#[tauri::command]
+pub fn get_spending_summary(
+    from: Option<String>,
+    to: Option<String>,
+    db: State<DbConnection>,
+) -> Result<SpendingSummary, String> {
+    let conn = db.0.lock().unwrap();
+    let (from, to) = normalize_period(&conn, from, to)?;
+    let purchases =
+        acquired_copies(&conn, from.as_deref(), to.as_deref()).map_err(|e| e.to_string())?;
+
+    let first_month = from
+        .as_deref()
+        .or(purchases.first().map(|p| p.acquired_at.as_str()))
+        .map(|date| date[..7].to_string());
+    let last_month = to
+        .as_deref()
+        .or(purchases.last().map(|p| p.acquired_at.as_str()))
+        .map(|date| date[..7].to_string());
+    let mut months: BTreeMap<String, (i64, i64)> = BTreeMap::new();
+    if let (Some(first), Some(last)) = (&first_month, &last_month) {
+        for month in month_range(first, last) {
+            months.insert(month, (0, 0));
+        }
+    }
+    for purchase in &purchases {
+        let entry = months.entry(purchase.acquired_at[..7].to_string()).or_default();
+        entry.0 += purchase.price;
+        entry.1 += 1;
+    }
+    let mut years: BTreeMap<String, (i64, i64)> = BTreeMap::new();
+    for (month, (spent, count)) in &months {
+        let entry = years.entry(month[..4].to_string()).or_default();
+        entry.0 += spent;
+        entry.1 += count;
+    }
+
+    let mut by_acquisition: Vec<AcquisitionSpending> =
+        [Some(AcquisitionKind::New), Some(AcquisitionKind::Used), Some(AcquisitionKind::Gift), None]
+            .into_iter()
+            .map(|acquisition| {
+                let matching = purchases.iter().filter(|p| p.acquisition == acquisition);
+                AcquisitionSpending {
+                    acquisition,
+                    spent: matching.clone().map(|p| p.price).sum(),
+                    copy_count: matching.count() as i64,
+                }
+            })
+            .collect();
+    by_acquisition.retain(|a| a.acquisition.is_some() || a.copy_count > 0);
+
+    Ok(SpendingSummary {
+        from,
+        to,
+        total_spent: purchases.iter().map(|p| p.price).sum(),
+        copy_count: purchases.len() as i64,
+        by_month: period_spending(months),
+        by_year: period_spending(years),
+        by_acquisition,
+        top_stores: ranking(
+            purchases
+                .iter()
+                .filter(|p| p.acquisition != Some(AcquisitionKind::Gift))
+                .filter_map(|p| p.store.as_deref()),
+        ),
+    })
+}
+
+/// 期間中に入手した冊
+struct AcquiredCopy {
+    acquired_at: String,
+    price: i64,
+    store: Option<String>,
+    acquisition: Option<AcquisitionKind>,
+}
+
+fn acquired_copies(
+    conn: &Connection,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<Vec<AcquiredCopy>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT acquired_at, COALESCE(purchase_price, 0), store, acquisition
+         FROM copies
+         WHERE acquired_at IS NOT NULL
+           AND book_id IN (SELECT id FROM books WHERE deleted_at IS NULL)
+           AND (?1 IS NULL OR acquired_at >= ?1)
+           AND (?2 IS NULL OR acquired_at <= ?2)
+         ORDER BY acquired_at",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![from, to], |row| {
+        Ok(AcquiredCopy {
+            acquired_at: row.get(0)?,
+            price: row.get(1)?,
+            store: row.get(2)?,
+            acquisition: acquisition_kind(row, 3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// 期間の両端を "YYYY-MM-DD" に揃え、逆転していないか確かめる。
+fn normalize_period(
+    conn: &Connection,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<(Option<String>, Option<String>), String> {
+    let from = from.map(|date| normalize_date(conn, Some(&date))).transpose()?;
+    let to = to.map(|date| normalize_date(conn, Some(&date))).transpose()?;
+    if let (Some(from), Some(to)) = (&from, &to) {
+        if from > to {
+            return Err("期間の開始日が終了日より後です".into());
+        }
+    }
+    Ok((from, to))
+}
+
+fn ended_sessions(
+    conn: &Connection,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<Vec<EndedSession>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT s.finished_at,
+                s.status = 'finished',
+                CASE WHEN s.status = 'finished' THEN COALESCE(s.total_pages, s.current_page)
+                     ELSE s.current_page END,
+                julianday(s.finished_at) - julianday(s.started_at),
+                b.author, b.publisher, g.name
+         FROM reading_sessions s
+         JOIN books b ON b.id = s.book_id AND b.deleted_at IS NULL
+         LEFT JOIN genres g ON g.id = b.genre_id
+         WHERE s.status IN ('finished', 'abandoned')
+           AND s.finished_at IS NOT NULL
+           AND (?1 IS NULL OR s.finished_at >= ?1)
+           AND (?2 IS NULL OR s.finished_at <= ?2)
+         ORDER BY s.finished_at",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![from, to], |row| {
+        Ok(EndedSession {
+            finished_at: row.get(0)?,
+            finished: row.get(1)?,
+            pages: row.get::<_, Option<i64>>(2)?.unwrap_or(0),
+            days: row.get(3)?,
+            author: row.get(4)?,
+            publisher: row.get(5)?,
+            genre: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn period_stats(periods: BTreeMap<String, (i64, i64)>) -> Vec<PeriodStat> {
+    periods
+        .into_iter()
+        .map(|(period, (finished_count, pages_read))| PeriodStat {
+            period,
+            finished_count,
+            pages_read,
+        })
+        .collect()
+}
+
+fn period_spending(periods: BTreeMap<String, (i64, i64)>) -> Vec<PeriodSpending> {
+    periods
+        .into_iter()
+        .map(|(period, (spent, copy_count))| PeriodSpending {
+            period,
+            spent,
+            copy_count,
+        })
+        .collect()
+}
+
+/// 出現回数の多い順（同数なら五十音順）に上位 TOP_N 件を返す。
+fn ranking<'a>(names: impl Iterator<Item = &'a str>) -> Vec<RankedCount> {
+    let mut counts: HashMap<&str, i64> = HashMap::new();
+    for name in names.map(str::trim).filter(|name| !name.is_empty()) {
+        *counts.entry(name).or_default() += 1;
+    }
+    let mut ranked: Vec<RankedCount> = counts
+        .into_iter()
+        .map(|(name, count)| RankedCount {
+            name: name.to_string(),
+            count,
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| compare_kana(&a.name, &b.name)));
+    ranked.truncate(TOP_N);
+    ranked
+}
+
+/// "YYYY-MM" の first から last まで（両端を含む）
+fn month_range(first: &str, last: &str) -> Vec<String> {
+    let parse = |month: &str| -> Option<(i32, u32)> {
+        Some((month[..4].parse().ok()?, month[5..7].parse().ok()?))
+    };
+    let (Some((mut year, mut month)), Some(end)) = (parse(first), parse(last)) else {
+        return Vec::new();
+    };
+    let mut months = Vec::new();
+    while (year, month) <= end {
+        months.push(format!("{:04}-{:02}", year, month));
+        month += 1;
+        if month > 12 {
+            year += 1;
+            month = 1;
+        }
+    }
+    months
+}
@@ -0,0 +1,103 @@
+use crate::db::DbConnection;
+use crate::models::Tag;
+use rusqlite::OptionalExtension;
+use tauri::State;
+
+#[tauri::command]
+pub fn get_tags(db: State<DbConnection>) -> Result<Vec<Tag>, String> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn
+        .prepare("SELECT id, name FROM tags ORDER BY name COLLATE KANA")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], row_to_tag).map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_book_tags(book_id: i64, db: State<DbConnection>) -> Result<Vec<Tag>, String> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.id, t.name FROM tags t
+             JOIN book_tags bt ON bt.tag_id = t.id
+             WHERE bt.book_id = ?1
+             ORDER BY t.name COLLATE KANA",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([book_id], row_to_tag).map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn add_tag(name: String, db: State<DbConnection>) -> Result<Tag, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("タグ名が空です".into());
+    }
+    let conn = db.0.lock().unwrap();
+    conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [name])
+        .map_err(|e| e.to_string())?;
+    conn.query_row("SELECT id, name FROM tags WHERE name = ?1", [name], row_to_tag)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn rename_tag(id: i64, name: String, db: State<DbConnection>) -> Result<Tag, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("タグ名が空です".into());
+    }
+    let conn = db.0.lock().unwrap();
+    let existing = conn
+        .query_row("SELECT id FROM tags WHERE name = ?1", [name], |row| row.get::<_, i64>(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if existing.is_some_and(|existing_id| existing_id != id) {
+        return Err(format!("タグ「{}」は既に存在します", name));
+    }
+    let affected = conn
+        .execute("UPDATE tags SET name = ?1 WHERE id = ?2", rusqlite::params![name, id])
+        .map_err(|e| e.to_string())?;
+    if affected == 0 {
+        return Err(format!("Tag with id {} not found", id));
+    }
+    Ok(Tag {
+        id,
+        name: name.to_string(),
+    })
+}
+
+#[tauri::command]
+pub fn delete_tag(id: i64, db: State<DbConnection>) -> Result<(), String> {
+    let mut conn = db.0.lock().unwrap();
+    crate::db::delete_tag(&mut conn, id).map_err(|e| e.to_string())
+}
+
+/// 選択した本すべてにタグを付ける。
+#[tauri::command]
+pub fn add_tags_to_books(
+    book_ids: Vec<i64>,
+    tag_ids: Vec<i64>,
+    db: State<DbConnection>,
+) -> Result<(), String> {
+    let mut conn = db.0.lock().unwrap();
+    crate::db::set_tags_on_books(&mut conn, &book_ids, &tag_ids, true).map_err(|e| e.to_string())
+}
+
+/// 選択した本すべてからタグを外す。
+#[tauri::command]
+pub fn remove_tags_from_books(
+    book_ids: Vec<i64>,
+    tag_ids: Vec<i64>,
+    db: State<DbConnection>,
+) -> Result<(), String> {
+    let mut conn = db.0.lock().unwrap();
+    crate::db::set_tags_on_books(&mut conn, &book_ids, &tag_ids, false).map_err(|e| e.to_string())
+}
+
+fn row_to_tag(row: &rusqlite::Row) -> rusqlite::Result<Tag> {
+    Ok(Tag {
+        id: row.get(0)?,
+        name: row.get(1)?,
+    })
+}
@@ -0,0 +1,110 @@
+use crate::commands::book::{get_book, row_to_book, BOOK_COLUMNS};
+use crate::commands::cover::remove_cover;
+use crate::commands::history::{last_delete_entry, restore_deleted};
+use crate::db::DbConnection;
+use crate::error::CommandError;
+use crate::models::{Book, TrashedBook};
+use rusqlite::Connection;
+use tauri::State;
+
+/// ゴミ箱に入れた本を残しておく日数。過ぎたものは起動時に完全に削除する。
+pub const TRASH_RETENTION_DAYS: i64 = 30;
+
+/// ゴミ箱の本を、削除した日時の新しい順に返す。
+#[tauri::command]
+pub fn get_trash(db: State<DbConnection>) -> Result<Vec<TrashedBook>, String> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {}, deleted_at, date(deleted_at, ?1) AS purge_on
+             FROM books WHERE deleted_at IS NOT NULL
+             ORDER BY deleted_at DESC, id DESC",
+            BOOK_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([retention_modifier()], |row| {
+            Ok(TrashedBook {
+                book: row_to_book(row)?,
+                deleted_at: row.get("deleted_at")?,
+                purge_on: row.get("purge_on")?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// ゴミ箱の本を元に戻す。ゴミ箱に無い本が1冊でも含まれていれば何もしない。
+#[tauri::command]
+pub fn restore_from_trash(book_ids: Vec<i64>, db: State<DbConnection>) -> Result<Vec<Book>, CommandError> {
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction()?;
+    for &book_id in &book_ids {
+        let trashed: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM books WHERE id = ?1 AND deleted_at IS NOT NULL)",
+            [book_id],
+            |row| row.get(0),
+        )?;
+        if !trashed {
+            return Err(CommandError::NotFound(format!(
+                "Book with id {} is not in the trash",
+                book_id
+            )));
+        }
+        match last_delete_entry(&tx, book_id)? {
+            Some(entry) => {
+                restore_deleted(&tx, &entry)?;
+            }
+            None => {
+                tx.execute("UPDATE books SET deleted_at = NULL WHERE id = ?1", [book_id])?;
+            }
+        }
+    }
+    tx.commit()?;
+    book_ids
+        .iter()
+        .map(|&id| get_book(&conn, id).map_err(CommandError::Database))
+        .collect()
+}
+
+/// ゴミ箱を空にし、完全に削除した本の数を返す。削除した本は履歴から戻せる。
+#[tauri::command]
+pub fn empty_trash(db: State<DbConnection>) -> Result<usize, String> {
+    let mut conn = db.0.lock().unwrap();
+    purge_trash(&mut conn, "deleted_at IS NOT NULL", []).map_err(|e| e.to_string())
+}
+
+/// ゴミ箱に入れてから `TRASH_RETENTION_DAYS` 日を過ぎた本を完全に削除する。起動時に呼ぶ。
+pub fn purge_expired_trash(conn: &mut Connection) -> Result<usize, rusqlite::Error> {
+    purge_trash(
+        conn,
+        "deleted_at < datetime('now', 'localtime', ?1)",
+        [format!("-{} days", TRASH_RETENTION_DAYS)],
+    )
+}
+
+fn purge_trash(
+    conn: &mut Connection,
+    condition: &str,
+    params: impl rusqlite::Params,
+) -> Result<usize, rusqlite::Error> {
+    let tx = conn.transaction()?;
+    let mut stmt = tx.prepare(&format!("SELECT id FROM books WHERE {}", condition))?;
+    let ids = stmt
+        .query_map(params, |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+    for &id in &ids {
+        crate::db::delete_book(&tx, id)?;
+    }
+    tx.commit()?;
+    for &id in &ids {
+        // 書影はファイルなので、消せなくても本の削除は取り消さない
+        let _ = remove_cover(id);
+    }
+    Ok(ids.len())
+}
+
+fn retention_modifier() -> String {
+    format!("+{} days", TRASH_RETENTION_DAYS)
+}
@@ -0,0 +1,251 @@
+use crate::commands::book::{get_book, insert_book, normalize_author_fields, normalize_title_kana};
+use crate::commands::copy::{non_empty, validate_copy};
+use crate::commands::reading::normalize_date;
+use crate::db::DbConnection;
+use crate::error::CommandError;
+use crate::normalize::normalize_isbn;
+use crate::models::{
+    AcquisitionKind, Book, NewBook, NewWishlistItem, PurchaseWishlistItem, UpdateWishlistItem,
+    WishlistItem,
+};
+use rusqlite::{Connection, OptionalExtension};
+use tauri::State;
+
+const WISHLIST_COLUMNS: &str = "id, isbn, title, title_kana, author, author_kana, publisher, price, \
+     c_code, series, volume, priority, target_price, notes, added_at";
+
+/// 欲しいものリストを優先度の高い順（同じ優先度なら追加した順）で返す。
+#[tauri::command]
+pub fn get_wishlist(db: State<DbConnection>) -> Result<Vec<WishlistItem>, String> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM wishlist ORDER BY priority, added_at, id",
+            WISHLIST_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], row_to_wishlist_item)
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// ISBN 検索（fetch_book_info_* の結果）などから欲しいものリストに追加する。
+/// 同じ ISBN が既にリストにあれば `conflict` エラーになる。
+#[tauri::command]
+pub fn add_wishlist_item(
+    item: NewWishlistItem,
+    db: State<DbConnection>,
+) -> Result<WishlistItem, CommandError> {
+    let priority = item.priority.unwrap_or(3);
+    validate_item(&item.title, priority, item.target_price)?;
+    let isbn = wishlist_isbn(item.isbn)?;
+    let (author, author_kana) =
+        normalize_author_fields(item.author.as_deref(), item.author_kana.as_deref());
+    let title_kana = normalize_title_kana(&item.title, item.title_kana.as_deref());
+    let conn = db.0.lock().unwrap();
+    conn.execute(
+        "INSERT INTO wishlist
+            (isbn, title, title_kana, author, author_kana, publisher, price, c_code,
+             series, volume, priority, target_price, notes, added_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, date('now', 'localtime'))",
+        rusqlite::params![
+            isbn,
+            item.title.trim(),
+            title_kana,
+            author,
+            author_kana,
+            non_empty(item.publisher),
+            item.price,
+            non_empty(item.c_code),
+            non_empty(item.series),
+            item.volume,
+            priority,
+            item.target_price,
+            non_empty(item.notes)
+        ],
+    )
+    .map_err(|e| {
+        CommandError::from_unique_violation(e, || "この本は既に欲しいものリストにあります".to_string())
+    })?;
+    get_wishlist_item(&conn, conn.last_insert_rowid())
+}
+
+#[tauri::command]
+pub fn update_wishlist_item(
+    item: UpdateWishlistItem,
+    db: State<DbConnection>,
+) -> Result<WishlistItem, CommandError> {
+    validate_item(&item.title, item.priority, item.target_price)?;
+    let isbn = wishlist_isbn(item.isbn)?;
+    let (author, author_kana) =
+        normalize_author_fields(item.author.as_deref(), item.author_kana.as_deref());
+    let title_kana = normalize_title_kana(&item.title, item.title_kana.as_deref());
+    let conn = db.0.lock().unwrap();
+    let affected = conn
+        .execute(
+            "UPDATE wishlist SET
+                isbn = ?1,
+                title = ?2,
+                title_kana = ?3,
+                author = ?4,
+                author_kana = ?5,
+                publisher = ?6,
+                price = ?7,
+                c_code = ?8,
+                series = ?9,
+                volume = ?10,
+                priority = ?11,
+                target_price = ?12,
+                notes = ?13
+             WHERE id = ?14",
+            rusqlite::params![
+                isbn,
+                item.title.trim(),
+                title_kana,
+                author,
+                author_kana,
+                non_empty(item.publisher),
+                item.price,
+                non_empty(item.c_code),
+                non_empty(item.series),
+                item.volume,
+                item.priority,
+                item.target_price,
+                non_empty(item.notes),
+                item.id
+            ],
+        )
+        .map_err(|e| {
+            CommandError::from_unique_violation(e, || {
+                "この本は既に欲しいものリストにあります".to_string()
+            })
+        })?;
+    if affected == 0 {
+        return Err(CommandError::NotFound(format!(
+            "Wishlist item with id {} not found",
+            item.id
+        )));
+    }
+    get_wishlist_item(&conn, item.id)
+}
+
+#[tauri::command]
+pub fn delete_wishlist_item(id: i64, db: State<DbConnection>) -> Result<(), String> {
+    let conn = db.0.lock().unwrap();
+    let affected = conn
+        .execute("DELETE FROM wishlist WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    if affected == 0 {
+        return Err(format!("Wishlist item with id {} not found", id));
+    }
+    Ok(())
+}
+
+/// 買った本を蔵書に移す。書誌情報とシリーズを引き継いで本を登録し、
+/// 1冊目に購入記録を付けてから、リストから取り除く。
+#[tauri::command]
+pub fn purchase_wishlist_item(
+    purchase: PurchaseWishlistItem,
+    db: State<DbConnection>,
+) -> Result<Book, String> {
+    let mut conn = db.0.lock().unwrap();
+    let item = get_wishlist_item(&conn, purchase.id).map_err(|e| e.to_string())?;
+    validate_copy(
+        &conn,
+        purchase.purchase_price,
+        purchase.acquisition,
+        purchase.location_id,
+    )?;
+    let acquired_at = normalize_date(&conn, purchase.acquired_at.as_deref())?;
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let book_id = insert_book(
+        &tx,
+        &NewBook {
+            title: item.title,
+            title_kana: item.title_kana,
+            genre_id: purchase.genre_id,
+            isbn: item.isbn,
+            author: item.author,
+            author_kana: item.author_kana,
+            publisher: item.publisher,
+            price: item.price,
+            c_code: item.c_code,
+            is_read: None,
+            series: item.series,
+            volume: item.volume,
+            location_id: purchase.location_id,
+            allow_duplicate: Some(true),
+        },
+    )?;
+    tx.execute(
+        "UPDATE copies SET acquired_at = ?1, purchase_price = ?2, store = ?3, acquisition = ?4
+         WHERE book_id = ?5",
+        rusqlite::params![
+            acquired_at,
+            purchase.purchase_price,
+            non_empty(purchase.store),
+            purchase.acquisition.map(AcquisitionKind::as_str),
+            book_id
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM wishlist WHERE id = ?1", [purchase.id])
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    get_book(&conn, book_id)
+}
+
+fn get_wishlist_item(conn: &Connection, id: i64) -> Result<WishlistItem, CommandError> {
+    conn.query_row(
+        &format!("SELECT {} FROM wishlist WHERE id = ?1", WISHLIST_COLUMNS),
+        [id],
+        row_to_wishlist_item,
+    )
+    .optional()?
+    .ok_or_else(|| CommandError::NotFound(format!("Wishlist item with id {} not found", id)))
+}
+
+fn validate_item(title: &str, priority: i64, target_price: Option<i64>) -> Result<(), CommandError> {
+    if title.trim().is_empty() {
+        return Err(CommandError::Invalid("タイトル必須".into()));
+    }
+    if !(1..=5).contains(&priority) {
+        return Err(CommandError::Invalid("優先度は1〜5で指定してください".into()));
+    }
+    if target_price.is_some_and(|price| price < 0) {
+        return Err(CommandError::Invalid("希望価格が不正です".into()));
+    }
+    Ok(())
+}
+
+/// 同じ本を表記違いで重複登録しないよう、ISBN は13桁に揃えて保存する。
+fn wishlist_isbn(isbn: Option<String>) -> Result<Option<String>, CommandError> {
+    match non_empty(isbn) {
+        Some(isbn) => normalize_isbn(&isbn)
+            .map(Some)
+            .ok_or_else(|| CommandError::Invalid(format!("ISBN「{}」が正しくありません", isbn))),
+        None => Ok(None),
+    }
+}
+
+fn row_to_wishlist_item(row: &rusqlite::Row) -> rusqlite::Result<WishlistItem> {
+    Ok(WishlistItem {
+        id: row.get(0)?,
+        isbn: row.get(1)?,
+        title: row.get(2)?,
+        title_kana: row.get(3)?,
+        author: row.get(4)?,
+        author_kana: row.get(5)?,
+        publisher: row.get(6)?,
+        price: row.get(7)?,
+        c_code: row.get(8)?,
+        series: row.get(9)?,
+        volume: row.get(10)?,
+        priority: row.get(11)?,
+        target_price: row.get(12)?,
+        notes: row.get(13)?,
+        added_at: row.get(14)?,
+    })
+}
@@ -1,20 +1,44 @@
-use rusqlite::{Connection, OptionalExtension};
-use std::{fs, sync::Mutex};
+use crate::collation;
+use crate::history::{record_change, record_update, snapshot_book, MERGED_INTO_KEY};
+use crate::models::{BookField, HistoryAction};
+use crate::normalize::{normalize_author, normalize_isbn, reading_from_kana, unify_width};
+use rusqlite::{Connection, DatabaseName, OptionalExtension};
+use std::{fs, path::PathBuf, sync::Mutex};
 
 pub struct DbConnection(pub Mutex<Connection>);
 
-pub fn setup_database(_app: &tauri::AppHandle) -> Result<Connection, rusqlite::Error> {
+/// データベースや書影を置くディレクトリ（実行ファイルの隣の data）
+pub fn data_dir() -> PathBuf {
     let exe_path = std::env::current_exe().expect("Failed to get current exe path");
     let app_dir = exe_path
         .parent()
         .expect("Failed to get parent dir")
         .to_path_buf();
-    let data_dir = app_dir.join("data");
+    app_dir.join("data")
+}
+
+pub fn setup_database(_app: &tauri::AppHandle) -> Result<Connection, rusqlite::Error> {
+    let data_dir = data_dir();
     if !data_dir.exists() {
         fs::create_dir_all(&data_dir).expect("Failed to create data dir");
     }
     let db_path = data_dir.join("bibly.sqlite");
-    let conn = Connection::open(&db_path)?;
+    let existed = db_path.exists();
+    let mut conn = Connection::open(&db_path)?;
+    collation::register(&conn)?;
+    if existed {
+        // マイグレーションに失敗しても戻せるよう、手を付ける前の状態を残しておく
+        if let Err(e) = backup_database(&conn) {
+            eprintln!("起動時のバックアップに失敗しました: {}", e);
+        }
+    }
+    create_base_tables(&conn)?;
+    migrate(&mut conn)?;
+    Ok(conn)
+}
+
+/// マイグレーションの前提になる最初のテーブル
+fn create_base_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
     conn.execute_batch(
         "
         CREATE TABLE IF NOT EXISTS genres (
@@ -34,15 +58,745 @@ pub fn setup_database(_app: &tauri::AppHandle) -> Result<Connection, rusqlite::E
             FOREIGN KEY (genre_id) REFERENCES genres (id)
         );
         ",
+    )
+}
+
+/// テスト用に、最新のスキーマのデータベースをメモリ上に作る。
+/// 同じ `name` で開けば同じデータベースになるので、別の接続から ATTACH もできる。
+#[cfg(test)]
+pub fn open_in_memory(name: &str) -> Connection {
+    let mut conn = Connection::open(format!("file:{}?mode=memory&cache=shared", name)).unwrap();
+    collation::register(&conn).unwrap();
+    create_base_tables(&conn).unwrap();
+    migrate(&mut conn).unwrap();
+    conn
+}
+
+/// 残しておくバックアップの世代数
+pub const BACKUP_GENERATIONS: usize = 10;
+
+pub fn backups_dir() -> PathBuf {
+    data_dir().join("backups")
+}
+
+/// オンラインバックアップで `backups/bibly-YYYYMMDD-HHMMSS.sqlite` に書き出し、
+/// 古いものから消して `BACKUP_GENERATIONS` 世代に保つ。書き出したファイルのパスを返す。
+pub fn backup_database(conn: &Connection) -> Result<PathBuf, String> {
+    let dir = backups_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let stamp: String = conn
+        .query_row(
+            "SELECT strftime('%Y%m%d-%H%M%S', 'now', 'localtime')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let path = dir.join(format!("bibly-{}.sqlite", stamp));
+    conn.backup(DatabaseName::Main, &path, None)
+        .map_err(|e| e.to_string())?;
+
+    // ファイル名に日時が入っているので、名前順に並べれば新しい順になる
+    let mut backups = backup_files().map_err(|e| e.to_string())?;
+    backups.sort_unstable_by(|a, b| b.cmp(a));
+    for old in backups.iter().skip(BACKUP_GENERATIONS) {
+        fs::remove_file(old).map_err(|e| e.to_string())?;
+    }
+    Ok(path)
+}
+
+/// `backups/` にあるバックアップファイル（順不同）
+pub fn backup_files() -> std::io::Result<Vec<PathBuf>> {
+    let dir = backups_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("bibly-") && name.ends_with(".sqlite"))
+        })
+        .collect())
+}
+
+/// このアプリが扱えるスキーマのバージョン（適用済みマイグレーションの数）
+pub fn schema_version() -> i64 {
+    MIGRATIONS.len() as i64
+}
+
+type Migration = fn(&Connection) -> Result<(), rusqlite::Error>;
+
+// Schema changes on top of the base tables above, applied in order.
+// `PRAGMA user_version` holds the number of migrations already applied.
+const MIGRATIONS: &[Migration] = &[
+    add_author_kana,
+    add_title_kana,
+    add_series,
+    add_tags,
+    add_genre_parent,
+    add_reading_sessions,
+    add_notes_and_reviews,
+    add_loans,
+    add_locations,
+    add_copies,
+    add_purchase_details,
+    add_wishlist,
+    add_isbn_index,
+    add_book_history,
+    add_trash,
+    add_book_revision,
+    add_timestamps,
+    skip_unchanged_read_flag,
+    add_author_index,
+    keep_book_ids_unique,
+    normalize_wishlist_isbns,
+];
+
+/// `?1` の場所とその下位の場所を `location_subtree(id)` として列挙する CTE
+pub const LOCATION_SUBTREE_CTE: &str = "WITH RECURSIVE location_subtree(id) AS (
+    SELECT ?1
+    UNION
+    SELECT l.id FROM locations l JOIN location_subtree s ON l.parent_id = s.id
+)";
+
+/// `?1` のジャンルとその子孫を `subtree(id)` として列挙する CTE
+pub const GENRE_SUBTREE_CTE: &str = "WITH RECURSIVE subtree(id) AS (
+    SELECT ?1
+    UNION
+    SELECT g.id FROM genres g JOIN subtree s ON g.parent_id = s.id
+)";
+
+pub fn migrate(conn: &mut Connection) -> Result<(), rusqlite::Error> {
+    let applied: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    // テーブルを作り直すマイグレーションでは、古いテーブルを消した時点で参照している行が
+    // 外部キー違反になるので、適用している間は検査を止める（トランザクションの中では切り替えられない）
+    conn.pragma_update(None, "foreign_keys", false)?;
+    let result = (|| {
+        for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied as usize) {
+            let tx = conn.transaction()?;
+            migration(&tx)?;
+            tx.pragma_update(None, "user_version", version as i64 + 1)?;
+            tx.commit()?;
+        }
+        Ok(())
+    })();
+    conn.pragma_update(None, "foreign_keys", true)?;
+    result
+}
+
+/// 著者の読みを保存する列を追加し、既存の著者名を正規化する。
+fn add_author_kana(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch("ALTER TABLE books ADD COLUMN author_kana TEXT;")?;
+
+    let mut stmt = conn.prepare("SELECT id, author FROM books WHERE author IS NOT NULL")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (id, author) in rows {
+        let normalized = Some(normalize_author(&author)).filter(|a| !a.is_empty());
+        let reading = normalized.as_deref().and_then(reading_from_kana);
+        conn.execute(
+            "UPDATE books SET author = ?1, author_kana = ?2 WHERE id = ?3",
+            rusqlite::params![normalized, reading, id],
+        )?;
+    }
+    Ok(())
+}
+
+/// 書名の読みを保存する列を追加する。かなだけの書名はそのまま読みにする。
+fn add_title_kana(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch("ALTER TABLE books ADD COLUMN title_kana TEXT;")?;
+
+    let mut stmt = conn.prepare("SELECT id, title FROM books")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (id, title) in rows {
+        if let Some(reading) = reading_from_kana(&title) {
+            conn.execute(
+                "UPDATE books SET title_kana = ?1 WHERE id = ?2",
+                rusqlite::params![reading, id],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// シリーズと巻数の対応表を追加する。1冊は1つのシリーズにだけ属する。
+fn add_series(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE series (
+            id          INTEGER PRIMARY KEY,
+            title       TEXT NOT NULL UNIQUE,
+            title_kana  TEXT
+        );
+        CREATE TABLE book_series (
+            book_id     INTEGER PRIMARY KEY,
+            series_id   INTEGER NOT NULL,
+            volume      INTEGER,
+            FOREIGN KEY (book_id) REFERENCES books (id),
+            FOREIGN KEY (series_id) REFERENCES series (id)
+        );
+        CREATE INDEX idx_book_series_series ON book_series (series_id, volume);
+        ",
+    )
+}
+
+/// ジャンルとは別に、本へ複数付けられるタグを追加する。
+fn add_tags(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE tags (
+            id      INTEGER PRIMARY KEY,
+            name    TEXT NOT NULL UNIQUE
+        );
+        CREATE TABLE book_tags (
+            book_id INTEGER NOT NULL,
+            tag_id  INTEGER NOT NULL,
+            PRIMARY KEY (book_id, tag_id),
+            FOREIGN KEY (book_id) REFERENCES books (id),
+            FOREIGN KEY (tag_id) REFERENCES tags (id)
+        );
+        CREATE INDEX idx_book_tags_tag ON book_tags (tag_id);
+        ",
+    )
+}
+
+/// ジャンルを入れ子にできるよう親ジャンルの列を追加する。NULL は最上位。
+fn add_genre_parent(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        ALTER TABLE genres ADD COLUMN parent_id INTEGER REFERENCES genres (id);
+        CREATE INDEX idx_genres_parent ON genres (parent_id);
+        ",
+    )
+}
+
+/// 読書記録（開始日・読了日・状態・ページ進捗）を追加する。
+/// `books.is_read` は読了済みのセッションがあるかどうかをトリガーで反映する派生値になる。
+fn add_reading_sessions(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE reading_sessions (
+            id              INTEGER PRIMARY KEY,
+            book_id         INTEGER NOT NULL,
+            status          TEXT NOT NULL DEFAULT 'reading'
+                            CHECK (status IN ('reading', 'finished', 'abandoned')),
+            started_at      TEXT,
+            finished_at     TEXT,
+            current_page    INTEGER,
+            total_pages     INTEGER,
+            FOREIGN KEY (book_id) REFERENCES books (id)
+        );
+        CREATE INDEX idx_reading_sessions_book ON reading_sessions (book_id);
+        CREATE INDEX idx_reading_sessions_status ON reading_sessions (status);
+
+        -- 既読の本は日付不明の読了記録として引き継ぐ
+        INSERT INTO reading_sessions (book_id, status)
+            SELECT id, 'finished' FROM books WHERE is_read = 1;
+
+        CREATE TRIGGER reading_sessions_after_insert AFTER INSERT ON reading_sessions
+        BEGIN
+            UPDATE books SET is_read = EXISTS(
+                SELECT 1 FROM reading_sessions WHERE book_id = NEW.book_id AND status = 'finished'
+            ) WHERE id = NEW.book_id;
+        END;
+        CREATE TRIGGER reading_sessions_after_update AFTER UPDATE OF status ON reading_sessions
+        BEGIN
+            UPDATE books SET is_read = EXISTS(
+                SELECT 1 FROM reading_sessions WHERE book_id = NEW.book_id AND status = 'finished'
+            ) WHERE id = NEW.book_id;
+        END;
+        CREATE TRIGGER reading_sessions_after_delete AFTER DELETE ON reading_sessions
+        BEGIN
+            UPDATE books SET is_read = EXISTS(
+                SELECT 1 FROM reading_sessions WHERE book_id = OLD.book_id AND status = 'finished'
+            ) WHERE id = OLD.book_id;
+        END;
+        ",
+    )
+}
+
+/// 全文検索の対象にする books の列（book_search の列名と同じ）
+pub const SEARCH_BOOK_COLUMNS: &[&str] = &[
+    "title",
+    "title_kana",
+    "author",
+    "author_kana",
+    "publisher",
+    "isbn",
+    "review",
+];
+
+/// 評価（0.5 刻みで 0.5〜5）とレビュー、メモ・引用のテーブル、全文検索用の索引を追加する。
+/// 索引は日本語を分かち書きせずに部分一致で引けるよう trigram で作り、トリガーで同期する。
+fn add_notes_and_reviews(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        ALTER TABLE books ADD COLUMN rating REAL
+            CHECK (rating IS NULL OR (rating BETWEEN 0.5 AND 5 AND rating * 2 = CAST(rating * 2 AS INTEGER)));
+        ALTER TABLE books ADD COLUMN review TEXT;
+        CREATE TABLE book_notes (
+            id          INTEGER PRIMARY KEY,
+            book_id     INTEGER NOT NULL,
+            kind        TEXT NOT NULL DEFAULT 'note' CHECK (kind IN ('note', 'quote')),
+            body        TEXT NOT NULL,
+            page        INTEGER,
+            created_at  TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+            FOREIGN KEY (book_id) REFERENCES books (id)
+        );
+        CREATE INDEX idx_book_notes_book ON book_notes (book_id);
+        ",
     )?;
-    Ok(conn)
+
+    let columns = SEARCH_BOOK_COLUMNS.join(", ");
+    conn.execute_batch(&format!(
+        "CREATE VIRTUAL TABLE book_search USING fts5({}, notes, tokenize = 'trigram');",
+        columns
+    ))?;
+    // 本1冊分の索引を作り直す SQL。{id} は NEW.id などに置き換える。
+    let refresh = format!(
+        "DELETE FROM book_search WHERE rowid = {{id}};
+         INSERT INTO book_search (rowid, {columns}, notes)
+             SELECT id, {columns},
+                    (SELECT group_concat(body, char(10)) FROM book_notes WHERE book_id = books.id)
+             FROM books WHERE id = {{id}};",
+        columns = columns
+    );
+    let triggers = [
+        ("books_search_after_insert", "AFTER INSERT ON books", "NEW.id"),
+        (
+            "books_search_after_update",
+            &format!("AFTER UPDATE OF {} ON books", columns),
+            "NEW.id",
+        ),
+        ("books_search_after_delete", "AFTER DELETE ON books", "OLD.id"),
+        ("book_notes_search_after_insert", "AFTER INSERT ON book_notes", "NEW.book_id"),
+        ("book_notes_search_after_update", "AFTER UPDATE ON book_notes", "NEW.book_id"),
+        ("book_notes_search_after_delete", "AFTER DELETE ON book_notes", "OLD.book_id"),
+    ];
+    for (name, event, id) in triggers {
+        conn.execute_batch(&format!(
+            "CREATE TRIGGER {} {} BEGIN {} END;",
+            name,
+            event,
+            refresh.replace("{id}", id)
+        ))?;
+    }
+    conn.execute_batch(&format!(
+        "INSERT INTO book_search (rowid, {columns}, notes) SELECT id, {columns}, NULL FROM books;",
+        columns = columns
+    ))
+}
+
+/// 貸出記録を追加する。返却されていない貸出は1冊につき1件まで。
+fn add_loans(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE loans (
+            id          INTEGER PRIMARY KEY,
+            book_id     INTEGER NOT NULL,
+            borrower    TEXT NOT NULL,
+            lent_at     TEXT NOT NULL,
+            due_at      TEXT,
+            returned_at TEXT,
+            FOREIGN KEY (book_id) REFERENCES books (id)
+        );
+        CREATE INDEX idx_loans_book ON loans (book_id);
+        CREATE UNIQUE INDEX idx_loans_outstanding ON loans (book_id) WHERE returned_at IS NULL;
+        ",
+    )
+}
+
+/// 本の置き場所（建物 > 部屋 > 棚）を追加する。
+fn add_locations(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE locations (
+            id          INTEGER PRIMARY KEY,
+            name        TEXT NOT NULL,
+            kind        TEXT NOT NULL CHECK (kind IN ('building', 'room', 'shelf')),
+            parent_id   INTEGER,
+            UNIQUE (parent_id, name),
+            FOREIGN KEY (parent_id) REFERENCES locations (id)
+        );
+        ALTER TABLE books ADD COLUMN location_id INTEGER REFERENCES locations (id);
+        CREATE INDEX idx_books_location ON books (location_id);
+        ",
+    )
+}
+
+/// 書誌情報（books）と手元の1冊（copies）を分ける。既存の本はそれぞれ1冊として引き継ぎ、
+/// 置き場所と貸出は冊単位に移す。
+fn add_copies(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE copies (
+            id              INTEGER PRIMARY KEY,
+            book_id         INTEGER NOT NULL,
+            condition       TEXT,
+            acquired_at     TEXT,
+            purchase_price  INTEGER,
+            location_id     INTEGER,
+            notes           TEXT,
+            FOREIGN KEY (book_id) REFERENCES books (id),
+            FOREIGN KEY (location_id) REFERENCES locations (id)
+        );
+        CREATE INDEX idx_copies_book ON copies (book_id);
+        CREATE INDEX idx_copies_location ON copies (location_id);
+        INSERT INTO copies (book_id, location_id) SELECT id, location_id FROM books ORDER BY id;
+
+        DROP INDEX idx_books_location;
+        ALTER TABLE books DROP COLUMN location_id;
+
+        ALTER TABLE loans ADD COLUMN copy_id INTEGER REFERENCES copies (id);
+        UPDATE loans SET copy_id = (SELECT MIN(id) FROM copies WHERE copies.book_id = loans.book_id);
+        DROP INDEX idx_loans_outstanding;
+        CREATE UNIQUE INDEX idx_loans_outstanding ON loans (copy_id) WHERE returned_at IS NULL;
+        ",
+    )
+}
+
+/// 実際の購入先と入手の種類（新品・古本・贈答）を冊ごとに記録する。
+fn add_purchase_details(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        ALTER TABLE copies ADD COLUMN store TEXT;
+        ALTER TABLE copies ADD COLUMN acquisition TEXT
+            CHECK (acquisition IN ('new', 'used', 'gift'));
+        CREATE INDEX idx_copies_acquired_at ON copies (acquired_at);
+        ",
+    )
+}
+
+/// まだ持っていない本の欲しいものリスト。蔵書（books）とは別のテーブルに置くので、
+/// 一覧や冊数の集計には入らない。
+fn add_wishlist(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE wishlist (
+            id            INTEGER PRIMARY KEY,
+            isbn          TEXT,
+            title         TEXT NOT NULL,
+            title_kana    TEXT,
+            author        TEXT,
+            author_kana   TEXT,
+            publisher     TEXT,
+            price         INTEGER,
+            c_code        TEXT,
+            series        TEXT,
+            volume        INTEGER,
+            priority      INTEGER NOT NULL DEFAULT 3 CHECK (priority BETWEEN 1 AND 5),
+            target_price  INTEGER,
+            notes         TEXT,
+            added_at      TEXT NOT NULL
+        );
+        CREATE UNIQUE INDEX idx_wishlist_isbn ON wishlist (isbn) WHERE isbn IS NOT NULL;
+        ",
+    )
+}
+
+/// 重複登録の確認で ISBN を引くための索引
+fn add_isbn_index(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch("CREATE INDEX idx_books_isbn ON books (isbn);")
+}
+
+/// 本の追加・変更・削除の履歴。変更前後の本を JSON のスナップショットで持つので、
+/// 本が消えた後も残るよう books への外部キーは付けない。
+fn add_book_history(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE book_history (
+            id          INTEGER PRIMARY KEY,
+            book_id     INTEGER NOT NULL,
+            action      TEXT NOT NULL CHECK (action IN ('insert', 'update', 'delete')),
+            title       TEXT NOT NULL,
+            before      TEXT,
+            after       TEXT,
+            changed_at  TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+            undone_at   TEXT
+        );
+        CREATE INDEX idx_book_history_book ON book_history (book_id);
+        ",
+    )
+}
+
+/// 削除した本をしばらくゴミ箱に残せるよう、削除日時の列を追加する。NULL はゴミ箱に無い本。
+fn add_trash(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        ALTER TABLE books ADD COLUMN deleted_at TEXT;
+        CREATE INDEX idx_books_deleted_at ON books (deleted_at);
+        ",
+    )
+}
+
+/// 楽観的排他制御に使う版番号。books の行を更新するたびにトリガーで1つ進める
+/// （更新で revision を明示的に変えた場合はそのまま）。
+fn add_book_revision(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        ALTER TABLE books ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;
+        CREATE TRIGGER books_bump_revision AFTER UPDATE ON books
+        WHEN NEW.revision = OLD.revision
+        BEGIN
+            UPDATE books SET revision = OLD.revision + 1 WHERE id = NEW.id;
+        END;
+        ",
+    )
+}
+
+/// 本とジャンルに登録日時・更新日時を追加する。どちらもトリガーで埋める。
+/// 既存の本の登録日時は、追加の履歴・最初の冊の入手日の順に分かるものを使い、
+/// どちらも無ければマイグレーションの日時にする。ジャンルはその中で最も古い本に合わせる。
+fn add_timestamps(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        DROP TRIGGER books_bump_revision;
+
+        ALTER TABLE books ADD COLUMN created_at TEXT;
+        ALTER TABLE books ADD COLUMN updated_at TEXT;
+        UPDATE books SET created_at = COALESCE(
+            (SELECT MAX(changed_at) FROM book_history
+             WHERE book_id = books.id AND action = 'insert'),
+            (SELECT datetime(MIN(acquired_at)) FROM copies WHERE book_id = books.id),
+            datetime('now', 'localtime')
+        );
+        UPDATE books SET updated_at = MAX(
+            created_at,
+            COALESCE((SELECT MAX(changed_at) FROM book_history WHERE book_id = books.id), '')
+        );
+        CREATE INDEX idx_books_created_at ON books (created_at);
+        CREATE INDEX idx_books_updated_at ON books (updated_at);
+
+        CREATE TRIGGER books_after_insert AFTER INSERT ON books
+        WHEN NEW.created_at IS NULL OR NEW.updated_at IS NULL
+        BEGIN
+            UPDATE books SET
+                created_at = COALESCE(NEW.created_at, datetime('now', 'localtime')),
+                updated_at = COALESCE(NEW.updated_at, NEW.created_at, datetime('now', 'localtime'))
+            WHERE id = NEW.id;
+        END;
+        -- 登録日時を埋めるだけの更新（OLD.created_at が NULL）は数えない
+        CREATE TRIGGER books_after_update AFTER UPDATE ON books
+        WHEN NEW.revision = OLD.revision AND OLD.created_at IS NOT NULL
+        BEGIN
+            UPDATE books SET
+                revision = OLD.revision + 1,
+                updated_at = datetime('now', 'localtime')
+            WHERE id = NEW.id;
+        END;
+
+        ALTER TABLE genres ADD COLUMN created_at TEXT;
+        ALTER TABLE genres ADD COLUMN updated_at TEXT;
+        UPDATE genres SET created_at = COALESCE(
+            (SELECT MIN(created_at) FROM books WHERE genre_id = genres.id),
+            datetime('now', 'localtime')
+        );
+        UPDATE genres SET updated_at = created_at;
+
+        CREATE TRIGGER genres_after_insert AFTER INSERT ON genres
+        WHEN NEW.created_at IS NULL OR NEW.updated_at IS NULL
+        BEGIN
+            UPDATE genres SET
+                created_at = COALESCE(NEW.created_at, datetime('now', 'localtime')),
+                updated_at = COALESCE(NEW.updated_at, NEW.created_at, datetime('now', 'localtime'))
+            WHERE id = NEW.id;
+        END;
+        CREATE TRIGGER genres_after_update AFTER UPDATE ON genres
+        WHEN NEW.updated_at IS OLD.updated_at AND OLD.created_at IS NOT NULL
+        BEGIN
+            UPDATE genres SET updated_at = datetime('now', 'localtime') WHERE id = NEW.id;
+        END;
+        ",
+    )
+}
+
+/// 読書記録のトリガーが、既読フラグが変わらないときにも books を更新して
+/// 版番号と更新日時を進めていたので、変わるときだけ更新するよう作り直す。
+fn skip_unchanged_read_flag(conn: &Connection) -> Result<(), rusqlite::Error> {
+    let is_read = |book_id: &str| {
+        format!(
+            "EXISTS(SELECT 1 FROM reading_sessions WHERE book_id = {} AND status = 'finished')",
+            book_id
+        )
+    };
+    let triggers = [
+        ("reading_sessions_after_insert", "AFTER INSERT ON reading_sessions", "NEW.book_id"),
+        ("reading_sessions_after_update", "AFTER UPDATE OF status ON reading_sessions", "NEW.book_id"),
+        ("reading_sessions_after_delete", "AFTER DELETE ON reading_sessions", "OLD.book_id"),
+    ];
+    for (name, event, book_id) in triggers {
+        conn.execute_batch(&format!(
+            "DROP TRIGGER {name};
+             CREATE TRIGGER {name} {event}
+             BEGIN
+                 UPDATE books SET is_read = {is_read} WHERE id = {book_id} AND is_read IS NOT {is_read};
+             END;",
+            name = name,
+            event = event,
+            book_id = book_id,
+            is_read = is_read(book_id)
+        ))?;
+    }
+    Ok(())
+}
+
+/// 重複登録の確認で、同じ著者の本だけを候補にするための索引
+fn add_author_index(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch("CREATE INDEX idx_books_author ON books (author);")
+}
+
+/// 完全に削除した本の id が新しい本に使い回されると、前の本の履歴が新しい本に付いてしまうので、
+/// books を AUTOINCREMENT で作り直す。採番は履歴に残っている最大の id の次から始める。
+fn keep_book_ids_unique(conn: &Connection) -> Result<(), rusqlite::Error> {
+    let table_sql: String = conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'books'",
+        [],
+        |row| row.get(0),
+    )?;
+    // 作り直すと消える索引とトリガーは、定義を控えておいて作り直す
+    let mut stmt = conn.prepare(
+        "SELECT sql FROM sqlite_master
+         WHERE tbl_name = 'books' AND type IN ('index', 'trigger') AND sql IS NOT NULL",
+    )?;
+    let dependents = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+
+    let columns = &table_sql[table_sql.find('(').unwrap_or(0)..];
+    let new_table = format!("CREATE TABLE books_new {}", columns)
+        .replacen("INTEGER PRIMARY KEY", "INTEGER PRIMARY KEY AUTOINCREMENT", 1);
+    // 他のテーブルのトリガーが books を参照しているので、作り直している間は名前の検査を止める
+    conn.execute_batch(&format!(
+        "{};
+         INSERT INTO books_new SELECT * FROM books;
+         PRAGMA legacy_alter_table = ON;
+         DROP TABLE books;
+         ALTER TABLE books_new RENAME TO books;
+         PRAGMA legacy_alter_table = OFF;",
+        new_table
+    ))?;
+    for sql in &dependents {
+        conn.execute_batch(sql)?;
+    }
+    conn.execute_batch(
+        "DELETE FROM sqlite_sequence WHERE name = 'books';
+         INSERT INTO sqlite_sequence (name, seq) VALUES ('books', MAX(
+             COALESCE((SELECT MAX(id) FROM books), 0),
+             COALESCE((SELECT MAX(book_id) FROM book_history), 0)
+         ));",
+    )
+}
+
+/// 欲しいものリストの ISBN を13桁に揃える。揃えると他の行と重なるものはそのまま残す。
+fn normalize_wishlist_isbns(conn: &Connection) -> Result<(), rusqlite::Error> {
+    let mut stmt = conn.prepare("SELECT id, isbn FROM wishlist WHERE isbn IS NOT NULL")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (id, isbn) in rows {
+        if let Some(normalized) = normalize_isbn(&isbn).filter(|n| *n != isbn) {
+            conn.execute(
+                "UPDATE OR IGNORE wishlist SET isbn = ?1 WHERE id = ?2",
+                rusqlite::params![normalized, id],
+            )?;
+        }
+    }
+    Ok(())
 }
 
 pub fn delete_book(conn: &Connection, id: i64) -> Result<usize, rusqlite::Error> {
+    conn.execute("DELETE FROM book_series WHERE book_id = ?", [id])?;
+    conn.execute("DELETE FROM book_tags WHERE book_id = ?", [id])?;
+    conn.execute("DELETE FROM reading_sessions WHERE book_id = ?", [id])?;
+    conn.execute("DELETE FROM book_notes WHERE book_id = ?", [id])?;
+    conn.execute("DELETE FROM loans WHERE book_id = ?", [id])?;
+    conn.execute("DELETE FROM copies WHERE book_id = ?", [id])?;
     let affected_rows = conn.execute("DELETE FROM books WHERE id = ?", [id])?;
     Ok(affected_rows)
 }
 
+/// 読了日のある読了記録が残っているか。あると `set_read_flag` では未読に戻せない。
+pub fn has_dated_reads(conn: &Connection, book_id: i64) -> Result<bool, rusqlite::Error> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM reading_sessions
+         WHERE book_id = ?1 AND status = 'finished'
+           AND (started_at IS NOT NULL OR finished_at IS NOT NULL))",
+        [book_id],
+        |row| row.get(0),
+    )
+}
+
+/// 既読フラグの切り替えを読書記録に反映する。
+/// 既読にすると日付不明の読了記録を1件作り、未読に戻すと日付不明の読了記録だけを取り消す。
+/// 読了日のある記録は残すので、それがあれば本は既読のまま（`is_read` を見て確かめる）。
+pub fn set_read_flag(conn: &Connection, book_id: i64, is_read: bool) -> Result<(), rusqlite::Error> {
+    let already_read: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM reading_sessions WHERE book_id = ?1 AND status = 'finished')",
+        [book_id],
+        |row| row.get(0),
+    )?;
+    if is_read && !already_read {
+        conn.execute(
+            "INSERT INTO reading_sessions (book_id, status) VALUES (?1, 'finished')",
+            [book_id],
+        )?;
+    } else if !is_read && already_read {
+        conn.execute(
+            "DELETE FROM reading_sessions
+             WHERE book_id = ?1 AND status = 'finished' AND started_at IS NULL AND finished_at IS NULL",
+            [book_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// 読書記録を別の本へ移したときなど、トリガーを通らずに記録が変わった本の既読フラグを合わせ直す。
+pub fn refresh_read_flag(conn: &Connection, book_id: i64) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "UPDATE books SET is_read = EXISTS(
+             SELECT 1 FROM reading_sessions WHERE book_id = ?1 AND status = 'finished'
+         ) WHERE id = ?1 AND is_read IS NOT EXISTS(
+             SELECT 1 FROM reading_sessions WHERE book_id = ?1 AND status = 'finished'
+         )",
+        [book_id],
+    )?;
+    Ok(())
+}
+
+/// シリーズ名から id を引く。まだ無ければ作成する。
+pub fn ensure_series(conn: &Connection, title: &str) -> Result<i64, rusqlite::Error> {
+    let title = unify_width(title);
+    conn.execute(
+        "INSERT OR IGNORE INTO series (title, title_kana) VALUES (?1, ?2)",
+        rusqlite::params![title, reading_from_kana(&title)],
+    )?;
+    conn.query_row("SELECT id FROM series WHERE title = ?1", [&title], |row| row.get(0))
+}
+
+/// 本をシリーズに割り当てる。`series_id` が None ならシリーズから外す。
+pub fn set_book_series(
+    conn: &Connection,
+    book_id: i64,
+    series_id: Option<i64>,
+    volume: Option<i64>,
+) -> Result<(), rusqlite::Error> {
+    match series_id {
+        Some(series_id) => conn.execute(
+            "INSERT INTO book_series (book_id, series_id, volume) VALUES (?1, ?2, ?3)
+             ON CONFLICT (book_id) DO UPDATE SET series_id = excluded.series_id, volume = excluded.volume",
+            rusqlite::params![book_id, series_id, volume],
+        )?,
+        None => conn.execute("DELETE FROM book_series WHERE book_id = ?1", [book_id])?,
+    };
+    Ok(())
+}
+
 pub fn delete_genre_and_unassign_books(conn: &mut Connection, genre_id: i64) -> Result<(), rusqlite::Error> {
     let tx = conn.transaction()?;
 
@@ -55,15 +809,319 @@ pub fn delete_genre_and_unassign_books(conn: &mut Connection, genre_id: i64) ->
         tx.execute("INSERT INTO genres (name) VALUES (?1)", ["未分類"])?;
         unclassified_id = Some(tx.last_insert_rowid());
     }
+    let unclassified_id = unclassified_id.unwrap();
 
-    // 2. Move books to "未分類" before deleting the genre.
+    // 2. Collect the genre and all of its descendants.
+    let mut subtree = genre_subtree_ids(&tx, genre_id)?;
+    let contains_unclassified = subtree.contains(&unclassified_id);
+    subtree.retain(|&id| id != unclassified_id);
+
+    // 3. Move books in the subtree to "未分類" before deleting the genres.
+    for id in &subtree {
+        tx.execute(
+            "UPDATE books SET genre_id = ?1 WHERE genre_id = ?2",
+            [unclassified_id, *id],
+        )?;
+    }
+
+    // 4. "未分類" itself survives as a top-level genre while it still holds books.
+    if contains_unclassified {
+        let remaining: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM books WHERE genre_id = ?1",
+            [unclassified_id],
+            |row| row.get(0),
+        )?;
+        if genre_id == unclassified_id && remaining == 0 {
+            tx.execute("DELETE FROM genres WHERE id = ?", [unclassified_id])?;
+        } else {
+            tx.execute("UPDATE genres SET parent_id = NULL WHERE id = ?", [unclassified_id])?;
+        }
+    }
+
+    // 5. Delete the genres themselves, children before their parents.
+    for id in subtree.iter().rev() {
+        tx.execute("DELETE FROM genres WHERE id = ?", [*id])?;
+    }
+
+    tx.commit()
+}
+
+/// 1冊を削除する。その冊の貸出履歴も消す。
+pub fn delete_copy(conn: &mut Connection, copy_id: i64) -> Result<(), rusqlite::Error> {
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM loans WHERE copy_id = ?", [copy_id])?;
+    tx.execute("DELETE FROM copies WHERE id = ?", [copy_id])?;
+    tx.commit()
+}
+
+/// 場所を削除し、そこにあった本（冊）は置き場所なしにする。下位の場所がある場合は呼び出し側で弾くこと。
+pub fn delete_location(conn: &mut Connection, location_id: i64) -> Result<usize, rusqlite::Error> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "UPDATE copies SET location_id = NULL WHERE location_id = ?1",
+        [location_id],
+    )?;
+    let affected = tx.execute("DELETE FROM locations WHERE id = ?1", [location_id])?;
+    tx.commit()?;
+    Ok(affected)
+}
+
+/// `source_id` の書籍と子ジャンルをすべて `target_id` に付け替えてから `source_id` を削除する。
+pub fn merge_genres(conn: &mut Connection, source_id: i64, target_id: i64) -> Result<(), rusqlite::Error> {
+    let tx = conn.transaction()?;
+
+    // 1. Move books in the source genre to the target.
     tx.execute(
         "UPDATE books SET genre_id = ?1 WHERE genre_id = ?2",
-        [unclassified_id.unwrap(), genre_id],
+        [target_id, source_id],
+    )?;
+
+    // 2. Child genres of the source become children of the target.
+    tx.execute(
+        "UPDATE genres SET parent_id = ?1 WHERE parent_id = ?2",
+        [target_id, source_id],
     )?;
 
-    // 3. Delete the genre itself.
-    tx.execute("DELETE FROM genres WHERE id = ?", [genre_id])?;
+    // 3. Delete the source genre itself.
+    tx.execute("DELETE FROM genres WHERE id = ?", [source_id])?;
 
     tx.commit()
 }
+
+/// `source_id` の本を `target_id` の本に統合する。手元の冊・貸出・読書記録・メモ・タグは
+/// すべて target に付け替えるので、履歴は失われない。
+pub fn merge_books(
+    conn: &mut Connection,
+    target_id: i64,
+    source_id: i64,
+    take_from_source: &[BookField],
+) -> Result<(), rusqlite::Error> {
+    let tx = conn.transaction()?;
+    let target_before = snapshot_book(&tx, target_id)?;
+    let mut source_before = snapshot_book(&tx, source_id)?;
+    // 統合を取り消すときに、移した冊や記録を複製せず target から戻せるよう印を付ける
+    if let Some(serde_json::Value::Object(snapshot)) = source_before.as_mut() {
+        snapshot.insert(MERGED_INTO_KEY.to_string(), target_id.into());
+    }
+
+    // 1. Copy the chosen fields from the source, and fill the target's blanks with it.
+    let assignments = [
+        BookField::Isbn,
+        BookField::Title,
+        BookField::TitleKana,
+        BookField::Author,
+        BookField::AuthorKana,
+        BookField::Publisher,
+        BookField::Price,
+        BookField::CCode,
+        BookField::GenreId,
+        BookField::Rating,
+        BookField::Review,
+    ]
+    .into_iter()
+    .filter_map(|field| {
+        let column = field.column()?;
+        Some(if take_from_source.contains(&field) {
+            format!("{0} = (SELECT {0} FROM books WHERE id = ?2)", column)
+        } else {
+            format!("{0} = COALESCE({0}, (SELECT {0} FROM books WHERE id = ?2))", column)
+        })
+    })
+    .collect::<Vec<_>>()
+    .join(", ");
+    tx.execute(
+        &format!("UPDATE books SET {} WHERE id = ?1", assignments),
+        [target_id, source_id],
+    )?;
+
+    // 2. Series: the source's entry wins only when chosen or when the target has none.
+    if take_from_source.contains(&BookField::Series) {
+        let source_has_series: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM book_series WHERE book_id = ?1)",
+            [source_id],
+            |row| row.get(0),
+        )?;
+        if source_has_series {
+            tx.execute("DELETE FROM book_series WHERE book_id = ?1", [target_id])?;
+        }
+    }
+    tx.execute(
+        "INSERT OR IGNORE INTO book_series (book_id, series_id, volume)
+         SELECT ?1, series_id, volume FROM book_series WHERE book_id = ?2",
+        [target_id, source_id],
+    )?;
+    tx.execute(
+        "INSERT OR IGNORE INTO book_tags (book_id, tag_id)
+         SELECT ?1, tag_id FROM book_tags WHERE book_id = ?2",
+        [target_id, source_id],
+    )?;
+
+    // 3. Move the history over.
+    for table in ["copies", "loans", "reading_sessions", "book_notes"] {
+        tx.execute(
+            &format!("UPDATE {} SET book_id = ?1 WHERE book_id = ?2", table),
+            [target_id, source_id],
+        )?;
+    }
+    refresh_read_flag(&tx, target_id)?;
+
+    // 4. Delete what is left of the source.
+    delete_book(&tx, source_id)?;
+
+    record_change(&tx, source_id, HistoryAction::Delete, source_before.as_ref(), None)?;
+    let target_after = snapshot_book(&tx, target_id)?;
+    record_change(
+        &tx,
+        target_id,
+        HistoryAction::Update,
+        target_before.as_ref(),
+        target_after.as_ref(),
+    )?;
+    tx.commit()
+}
+
+/// ジャンルとその子孫すべての id（自身を含む）。親は常に子より前に並ぶ。
+pub fn genre_subtree_ids(conn: &Connection, genre_id: i64) -> Result<Vec<i64>, rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!("{} SELECT id FROM subtree", GENRE_SUBTREE_CTE))?;
+    let ids = stmt
+        .query_map([genre_id], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ids)
+}
+
+pub fn delete_tag(conn: &mut Connection, tag_id: i64) -> Result<(), rusqlite::Error> {
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM book_tags WHERE tag_id = ?", [tag_id])?;
+    tx.execute("DELETE FROM tags WHERE id = ?", [tag_id])?;
+    tx.commit()
+}
+
+/// 選択した本すべてにタグを付ける（`attach` が false なら外す）。付け外しした本ごとに履歴を残す。
+pub fn set_tags_on_books(
+    conn: &mut Connection,
+    book_ids: &[i64],
+    tag_ids: &[i64],
+    attach: bool,
+) -> Result<(), rusqlite::Error> {
+    let tx = conn.transaction()?;
+    {
+        let sql = if attach {
+            "INSERT OR IGNORE INTO book_tags (book_id, tag_id) VALUES (?1, ?2)"
+        } else {
+            "DELETE FROM book_tags WHERE book_id = ?1 AND tag_id = ?2"
+        };
+        let mut stmt = tx.prepare(sql)?;
+        for &book_id in book_ids {
+            let before = snapshot_book(&tx, book_id)?;
+            for tag_id in tag_ids {
+                stmt.execute([&book_id, tag_id])?;
+            }
+            record_update(&tx, book_id, before.as_ref())?;
+        }
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `add_locations` までを当てた、置き場所と貸出が本に付いていた頃のデータベース
+    fn database_before_copies() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        collation::register(&conn).unwrap();
+        create_base_tables(&conn).unwrap();
+        let before_copies = 9;
+        for migration in &MIGRATIONS[..before_copies] {
+            migration(&conn).unwrap();
+        }
+        conn.pragma_update(None, "user_version", before_copies as i64).unwrap();
+        conn.execute_batch(
+            "INSERT INTO locations (id, name, kind) VALUES (1, '書斎', 'building');
+             INSERT INTO books (id, title, author, location_id) VALUES (1, '坊っちゃん', '夏目漱石', 1);
+             INSERT INTO books (id, title, author, location_id) VALUES (2, '羅生門', '芥川龍之介', NULL);
+             INSERT INTO loans (book_id, borrower, lent_at) VALUES (1, '山田', '2024-01-01');
+             INSERT INTO loans (book_id, borrower, lent_at, returned_at)
+                 VALUES (2, '佐藤', '2024-01-01', '2024-02-01');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn migrate_moves_locations_and_loans_to_copies() {
+        let mut conn = database_before_copies();
+        migrate(&mut conn).unwrap();
+
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, schema_version());
+        let copies: Vec<(i64, Option<i64>)> = conn
+            .prepare("SELECT book_id, location_id FROM copies ORDER BY book_id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(copies, [(1, Some(1)), (2, None)]);
+        let unmatched_loans: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM loans
+                 WHERE copy_id IS NULL OR copy_id NOT IN (SELECT id FROM copies WHERE book_id = loans.book_id)",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(unmatched_loans, 0);
+        let location_column: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('books') WHERE name = 'location_id'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(location_column, 0);
+    }
+
+    #[test]
+    fn migrate_keeps_book_indexes_and_triggers() {
+        let mut conn = database_before_copies();
+        migrate(&mut conn).unwrap();
+
+        let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE tbl_name = 'books' AND type IN ('index', 'trigger')")
+            .unwrap();
+        let names: Vec<String> = stmt
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        for name in [
+            "idx_books_isbn",
+            "idx_books_author",
+            "idx_books_created_at",
+            "books_after_insert",
+            "books_after_update",
+        ] {
+            assert!(names.iter().any(|n| n == name), "{} が無くなっている", name);
+        }
+
+        conn.execute("UPDATE books SET title = 'ぼっちゃん' WHERE id = 1", []).unwrap();
+        let (revision, updated_at): (i64, Option<String>) = conn
+            .query_row("SELECT revision, updated_at FROM books WHERE id = 1", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(revision, 1);
+        assert!(updated_at.is_some());
+    }
+
+    #[test]
+    fn purged_book_ids_are_not_reused() {
+        let mut conn = database_before_copies();
+        migrate(&mut conn).unwrap();
+
+        delete_book(&conn, 2).unwrap();
+        conn.execute("INSERT INTO books (title) VALUES ('こころ')", []).unwrap();
+        assert_eq!(conn.last_insert_rowid(), 3);
+    }
+}
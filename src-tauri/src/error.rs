@@ -0,0 +1,59 @@
+// Typed errors for commands whose failures the UI needs to tell apart.
+//
+// Serialized as `{ "kind": "conflict", "message": "..." }` so the frontend can branch on `kind`
+// and still show `message` as-is.
+use crate::models::DuplicateMatch;
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum CommandError {
+    /// 名前の重複など UNIQUE 制約に反する変更や、読み込んだ後に他で更新された本への変更
+    Conflict(String),
+    NotFound(String),
+    /// 入力値が不正
+    Invalid(String),
+    Database(String),
+    /// 同じ本らしき登録がある。`message` には重複候補の一覧が入る。
+    Duplicate(Vec<DuplicateMatch>),
+}
+
+impl CommandError {
+    /// UNIQUE 制約違反なら `conflict` を、それ以外は `database` エラーを返す。
+    pub fn from_unique_violation(err: rusqlite::Error, conflict: impl FnOnce() -> String) -> Self {
+        if is_unique_violation(&err) {
+            CommandError::Conflict(conflict())
+        } else {
+            err.into()
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Conflict(message)
+            | CommandError::NotFound(message)
+            | CommandError::Invalid(message)
+            | CommandError::Database(message) => f.write_str(message),
+            CommandError::Duplicate(_) => f.write_str("同じ本が既に登録されています"),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<rusqlite::Error> for CommandError {
+    fn from(err: rusqlite::Error) -> Self {
+        CommandError::Database(err.to_string())
+    }
+}
+
+fn is_unique_violation(err: &rusqlite::Error) -> bool {
+    matches!(
+        err,
+        rusqlite::Error::SqliteFailure(e, _)
+            if e.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE
+    )
+}
@@ -0,0 +1,474 @@
+// Snapshots of a book for the change history (book_history).
+//
+// A snapshot holds the book row and every row attached to it, keyed by table name
+// ({"books": [{...}], "copies": [...], ...}). Rows are stored column by column, so snapshots
+// taken before a column was added can still be restored.
+//
+// The snapshot of a book merged into another also holds "merged_into": the id of the book that
+// took over its copies and records, so that restoring it moves those rows back instead of
+// duplicating them.
+use crate::models::HistoryAction;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use serde_json::{Map, Value as Json};
+use std::collections::HashMap;
+
+/// スナップショットに含めるテーブルと、本の id を持つ列。復元はこの順に行う。
+const BOOK_TABLES: &[(&str, &str)] = &[
+    ("books", "id"),
+    ("book_series", "book_id"),
+    ("book_tags", "book_id"),
+    ("copies", "book_id"),
+    ("reading_sessions", "book_id"),
+    ("book_notes", "book_id"),
+    ("loans", "book_id"),
+];
+
+/// 変更を取り消しても戻さない books の列
+const KEPT_ON_REVERT: &[&str] = &["id", "is_read", "revision"];
+
+/// 統合で消した本のスナップショットに、統合先の本の id を入れるキー
+pub const MERGED_INTO_KEY: &str = "merged_into";
+
+/// 統合で統合先に移した行のテーブル
+const MERGED_TABLES: &[&str] = &["copies", "loans", "reading_sessions", "book_notes"];
+
+/// 本とそれに付随する行のスナップショット。本が無ければ None。
+pub fn snapshot_book(conn: &Connection, book_id: i64) -> Result<Option<Json>, rusqlite::Error> {
+    let mut snapshot = Map::new();
+    for (table, key) in BOOK_TABLES {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM {} WHERE {} = ?1 ORDER BY rowid",
+            table, key
+        ))?;
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let rows = stmt
+            .query_map([book_id], |row| {
+                let mut object = Map::new();
+                for (i, column) in columns.iter().enumerate() {
+                    object.insert(column.clone(), to_json(row.get(i)?));
+                }
+                Ok(Json::Object(object))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        if *table == "books" && rows.is_empty() {
+            return Ok(None);
+        }
+        snapshot.insert(table.to_string(), Json::Array(rows));
+    }
+    Ok(Some(Json::Object(snapshot)))
+}
+
+/// 履歴に1件残す。書名は変更後（削除なら変更前）のスナップショットから取る。
+pub fn record_change(
+    conn: &Connection,
+    book_id: i64,
+    action: HistoryAction,
+    before: Option<&Json>,
+    after: Option<&Json>,
+) -> Result<(), rusqlite::Error> {
+    let title = after
+        .or(before)
+        .and_then(|snapshot| snapshot["books"][0]["title"].as_str())
+        .unwrap_or_default();
+    conn.execute(
+        "INSERT INTO book_history (book_id, action, title, before, after)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![
+            book_id,
+            action.as_str(),
+            title,
+            before.map(Json::to_string),
+            after.map(Json::to_string)
+        ],
+    )?;
+    Ok(())
+}
+
+/// 変更前のスナップショット `before` から本が変わっていれば、変更の履歴を残す。
+pub fn record_update(conn: &Connection, book_id: i64, before: Option<&Json>) -> Result<(), rusqlite::Error> {
+    let after = snapshot_book(conn, book_id)?;
+    if before != after.as_ref() {
+        record_change(conn, book_id, HistoryAction::Update, before, after.as_ref())?;
+    }
+    Ok(())
+}
+
+/// 削除した本をスナップショットから元に戻し、戻した本の id を返す。
+/// 元の id が他の本に使われていれば新しい id を振る。既に無いジャンル・置き場所は外し、
+/// 既に無いシリーズ・タグへの割り当てと、冊の戻らなかった貸出は戻さない。統合で消した本なら、
+/// 統合先にまだある冊・貸出・読書記録・メモは作り直さずに戻した本へ移す。
+pub fn restore_book(conn: &Connection, snapshot: &Json) -> Result<i64, String> {
+    let merged_into = snapshot.get(MERGED_INTO_KEY).and_then(Json::as_i64);
+    let mut book_id = None;
+    let mut copy_ids: HashMap<i64, i64> = HashMap::new();
+    for (table, key) in BOOK_TABLES {
+        for row in snapshot_rows(snapshot, table)? {
+            let mut row = row.clone();
+            if let Some(book_id) = book_id {
+                row.insert(key.to_string(), Json::from(book_id));
+            }
+            if let (Some(target_id), Some(book_id)) = (merged_into, book_id) {
+                if MERGED_TABLES.contains(table) && move_back(conn, table, &row, target_id, book_id)? {
+                    if *table == "copies" {
+                        if let Some(id) = row.get("id").and_then(Json::as_i64) {
+                            copy_ids.insert(id, id);
+                        }
+                    }
+                    continue;
+                }
+            }
+            match *table {
+                "books" => clear_missing(conn, &mut row, "genre_id", "genres")?,
+                "copies" => clear_missing(conn, &mut row, "location_id", "locations")?,
+                "book_series" if !references_exist(conn, &row, "series_id", "series")? => continue,
+                "book_tags" if !references_exist(conn, &row, "tag_id", "tags")? => continue,
+                "loans" => {
+                    // 冊が戻らなかった貸出は、どの冊の貸出か分からないので戻さない
+                    let Some(&copy_id) = row["copy_id"].as_i64().and_then(|id| copy_ids.get(&id)) else {
+                        continue;
+                    };
+                    row.insert("copy_id".into(), copy_id.into());
+                }
+                _ => {}
+            }
+            let old_id = row.get("id").and_then(Json::as_i64);
+            if let Some(old_id) = old_id {
+                if exists(conn, table, old_id)? {
+                    row.remove("id");
+                }
+            }
+            let new_id = insert_row(conn, table, &row)?;
+            match *table {
+                "books" => book_id = Some(new_id),
+                "copies" => {
+                    copy_ids.insert(old_id.unwrap_or(new_id), new_id);
+                }
+                _ => {}
+            }
+        }
+    }
+    if let Some(target_id) = merged_into {
+        crate::db::refresh_read_flag(conn, target_id).map_err(|e| e.to_string())?;
+    }
+    book_id.ok_or_else(|| "履歴のスナップショットに本がありません".to_string())
+}
+
+/// 統合先 `target_id` にまだある行なら `book_id` の本へ移して true を返す。
+fn move_back(
+    conn: &Connection,
+    table: &str,
+    row: &Map<String, Json>,
+    target_id: i64,
+    book_id: i64,
+) -> Result<bool, String> {
+    let Some(id) = row.get("id").and_then(Json::as_i64) else {
+        return Ok(false);
+    };
+    let moved = conn
+        .execute(
+            &format!("UPDATE {} SET book_id = ?1 WHERE id = ?2 AND book_id = ?3", table),
+            [book_id, id, target_id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(moved > 0)
+}
+
+/// 本の書誌情報・シリーズ・タグ・メモ・冊の置き場所・既読フラグをスナップショットの状態に戻す。
+/// 冊・読書記録・貸出そのものはそのまま残す。版番号は戻さず、戻したことも1回の更新として数える。
+pub fn revert_book(conn: &Connection, book_id: i64, snapshot: &Json) -> Result<(), String> {
+    let mut book = snapshot_rows(snapshot, "books")?
+        .first()
+        .copied()
+        .ok_or("履歴のスナップショットに本がありません")?
+        .clone();
+    clear_missing(conn, &mut book, "genre_id", "genres")?;
+    let is_read = book.get("is_read").and_then(Json::as_i64).unwrap_or(0) != 0;
+    let columns: Vec<String> = table_columns(conn, "books")?
+        .into_iter()
+        .filter(|column| !KEPT_ON_REVERT.contains(&column.as_str()) && book.contains_key(column))
+        .collect();
+    let assignments: Vec<String> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| format!("{} = ?{}", column, i + 1))
+        .collect();
+    let mut values: Vec<Value> = columns.iter().map(|column| to_value(&book[column])).collect();
+    values.push(Value::Integer(book_id));
+    conn.execute(
+        &format!(
+            "UPDATE books SET {} WHERE id = ?{}",
+            assignments.join(", "),
+            values.len()
+        ),
+        rusqlite::params_from_iter(values),
+    )
+    .map_err(|e| e.to_string())?;
+
+    for (table, reference) in [
+        ("book_series", Some(("series_id", "series"))),
+        ("book_tags", Some(("tag_id", "tags"))),
+        ("book_notes", None),
+    ] {
+        conn.execute(&format!("DELETE FROM {} WHERE book_id = ?1", table), [book_id])
+            .map_err(|e| e.to_string())?;
+        for row in snapshot_rows(snapshot, table)? {
+            if let Some((column, parent)) = reference {
+                if !references_exist(conn, row, column, parent)? {
+                    continue;
+                }
+            }
+            let mut row = row.clone();
+            row.insert("book_id".into(), Json::from(book_id));
+            if let Some(id) = row.get("id").and_then(Json::as_i64) {
+                if exists(conn, table, id)? {
+                    row.remove("id");
+                }
+            }
+            insert_row(conn, table, &row)?;
+        }
+    }
+    for copy in snapshot_rows(snapshot, "copies")? {
+        let mut copy = copy.clone();
+        clear_missing(conn, &mut copy, "location_id", "locations")?;
+        conn.execute(
+            "UPDATE copies SET location_id = ?1 WHERE id = ?2 AND book_id = ?3",
+            rusqlite::params![
+                to_value(&copy["location_id"]),
+                copy.get("id").and_then(Json::as_i64),
+                book_id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    crate::db::set_read_flag(conn, book_id, is_read).map_err(|e| e.to_string())
+}
+
+fn snapshot_rows<'a>(snapshot: &'a Json, table: &str) -> Result<Vec<&'a Map<String, Json>>, String> {
+    match snapshot.get(table) {
+        None => Ok(Vec::new()),
+        Some(Json::Array(rows)) => rows
+            .iter()
+            .map(|row| row.as_object().ok_or_else(broken_snapshot))
+            .collect(),
+        Some(_) => Err(broken_snapshot()),
+    }
+}
+
+fn broken_snapshot() -> String {
+    "履歴のスナップショットが壊れています".to_string()
+}
+
+/// 今のテーブルにある列だけを使って1行挿入し、その行の id を返す。
+fn insert_row(conn: &Connection, table: &str, row: &Map<String, Json>) -> Result<i64, String> {
+    let columns: Vec<String> = table_columns(conn, table)?
+        .into_iter()
+        .filter(|column| row.contains_key(column))
+        .collect();
+    let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{}", i)).collect();
+    conn.execute(
+        &format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            table,
+            columns.join(", "),
+            placeholders.join(", ")
+        ),
+        rusqlite::params_from_iter(columns.iter().map(|column| to_value(&row[column]))),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| e.to_string())?;
+    let columns = stmt
+        .query_map([], |row| row.get(1))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string());
+    columns
+}
+
+fn exists(conn: &Connection, table: &str, id: i64) -> Result<bool, String> {
+    conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM {} WHERE id = ?1)", table),
+        [id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// `column` が指す `parent` の行があるか（NULL なら true）
+fn references_exist(
+    conn: &Connection,
+    row: &Map<String, Json>,
+    column: &str,
+    parent: &str,
+) -> Result<bool, String> {
+    match row.get(column).and_then(Json::as_i64) {
+        Some(id) => exists(conn, parent, id),
+        None => Ok(true),
+    }
+}
+
+/// `column` が指す `parent` の行が既に無ければ NULL にする。
+fn clear_missing(
+    conn: &Connection,
+    row: &mut Map<String, Json>,
+    column: &str,
+    parent: &str,
+) -> Result<(), String> {
+    if !references_exist(conn, row, column, parent)? {
+        row.insert(column.to_string(), Json::Null);
+    }
+    Ok(())
+}
+
+fn to_json(value: Value) -> Json {
+    match value {
+        Value::Null | Value::Blob(_) => Json::Null,
+        Value::Integer(i) => Json::from(i),
+        Value::Real(f) => Json::from(f),
+        Value::Text(s) => Json::String(s),
+    }
+}
+
+fn to_value(json: &Json) -> Value {
+    match json {
+        Json::Null => Value::Null,
+        Json::Bool(b) => Value::Integer(i64::from(*b)),
+        Json::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Real(n.as_f64().unwrap_or_default()),
+        },
+        Json::String(s) => Value::Text(s.clone()),
+        other => Value::Text(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{merge_books, open_in_memory};
+
+    fn ids(conn: &Connection, sql: &str, book_id: i64) -> Vec<i64> {
+        conn.prepare(sql)
+            .unwrap()
+            .query_map([book_id], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    /// 1 に 2 を統合し、統合で消えた 2 のスナップショットを返す
+    fn merge_second_into_first(conn: &mut Connection) -> Json {
+        conn.execute_batch(
+            "INSERT INTO books (id, title) VALUES (1, '坊っちゃん'), (2, '坊っちゃん（文庫）');
+             INSERT INTO tags (id, name) VALUES (1, 'お気に入り');
+             INSERT INTO book_tags (book_id, tag_id) VALUES (2, 1);
+             INSERT INTO copies (id, book_id) VALUES (1, 1), (2, 2);
+             INSERT INTO loans (book_id, copy_id, borrower, lent_at) VALUES (2, 2, '山田', '2024-01-01');
+             INSERT INTO reading_sessions (book_id, status, finished_at) VALUES (2, 'finished', '2024-02-01');
+             INSERT INTO book_notes (book_id, body) VALUES (2, '親譲りの無鉄砲');",
+        )
+        .unwrap();
+        merge_books(conn, 1, 2, &[]).unwrap();
+        let before: String = conn
+            .query_row(
+                "SELECT before FROM book_history WHERE book_id = 2 AND action = 'delete'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        serde_json::from_str(&before).unwrap()
+    }
+
+    #[test]
+    fn restoring_merged_book_moves_rows_back_from_target() {
+        let mut conn = open_in_memory("restore_moves_back");
+        let snapshot = merge_second_into_first(&mut conn);
+        assert_eq!(snapshot[MERGED_INTO_KEY], 1);
+
+        assert_eq!(restore_book(&conn, &snapshot).unwrap(), 2);
+        assert_eq!(ids(&conn, "SELECT id FROM copies WHERE book_id = ?1", 1), [1]);
+        assert_eq!(ids(&conn, "SELECT id FROM copies WHERE book_id = ?1", 2), [2]);
+        for table in ["loans", "reading_sessions", "book_notes"] {
+            // 統合先に残らず、複製もされていない
+            let sql = format!("SELECT book_id FROM {} WHERE book_id IN (1, ?1)", table);
+            assert_eq!(ids(&conn, &sql, 2), [2], "{}", table);
+        }
+        assert_eq!(ids(&conn, "SELECT copy_id FROM loans WHERE book_id = ?1", 2), [2]);
+        assert_eq!(ids(&conn, "SELECT tag_id FROM book_tags WHERE book_id = ?1", 2), [1]);
+        assert_eq!(ids(&conn, "SELECT is_read FROM books WHERE id = ?1", 1), [0]);
+        assert_eq!(ids(&conn, "SELECT is_read FROM books WHERE id = ?1", 2), [1]);
+    }
+
+    #[test]
+    fn restoring_merged_book_recreates_rows_when_target_is_gone() {
+        let mut conn = open_in_memory("restore_recreates");
+        let snapshot = merge_second_into_first(&mut conn);
+        crate::db::delete_book(&conn, 1).unwrap();
+        // 消えた冊の id を別の本の冊が使っている
+        conn.execute_batch(
+            "INSERT INTO books (id, title) VALUES (3, 'こころ');
+             INSERT INTO copies (id, book_id) VALUES (1, 3), (2, 3);",
+        )
+        .unwrap();
+
+        let book_id = restore_book(&conn, &snapshot).unwrap();
+        let copies = ids(&conn, "SELECT id FROM copies WHERE book_id = ?1", book_id);
+        assert_eq!(copies.len(), 1);
+        assert_ne!(copies[0], 2);
+        assert_eq!(ids(&conn, "SELECT copy_id FROM loans WHERE book_id = ?1", book_id), copies);
+        assert_eq!(ids(&conn, "SELECT id FROM copies WHERE book_id = ?1", 3), [1, 2]);
+        assert_eq!(ids(&conn, "SELECT id FROM reading_sessions WHERE book_id = ?1", book_id).len(), 1);
+        assert_eq!(ids(&conn, "SELECT id FROM book_notes WHERE book_id = ?1", book_id).len(), 1);
+    }
+
+    #[test]
+    fn revert_book_restores_details_but_keeps_revision() {
+        let conn = open_in_memory("revert");
+        conn.execute_batch(
+            "INSERT INTO books (id, title) VALUES (1, '坊っちゃん');
+             INSERT INTO tags (id, name) VALUES (1, '名作'), (2, '再読');
+             INSERT INTO book_tags (book_id, tag_id) VALUES (1, 1);
+             INSERT INTO series (id, title) VALUES (1, '漱石全集');
+             INSERT INTO book_series (book_id, series_id, volume) VALUES (1, 1, 2);
+             INSERT INTO locations (id, name, kind) VALUES (1, '書斎', 'building'), (2, '居間', 'building');
+             INSERT INTO copies (id, book_id, location_id) VALUES (1, 1, 1);
+             INSERT INTO book_notes (book_id, body) VALUES (1, '親譲りの無鉄砲');",
+        )
+        .unwrap();
+        let snapshot = snapshot_book(&conn, 1).unwrap().unwrap();
+        conn.execute_batch(
+            "UPDATE books SET title = 'ぼっちゃん' WHERE id = 1;
+             DELETE FROM book_tags WHERE book_id = 1;
+             INSERT INTO book_tags (book_id, tag_id) VALUES (1, 2);
+             UPDATE book_series SET volume = 3 WHERE book_id = 1;
+             UPDATE copies SET location_id = 2 WHERE id = 1;
+             DELETE FROM book_notes WHERE book_id = 1;
+             INSERT INTO copies (id, book_id) VALUES (2, 1);",
+        )
+        .unwrap();
+        let revision: i64 = conn
+            .query_row("SELECT revision FROM books WHERE id = 1", [], |row| row.get(0))
+            .unwrap();
+
+        revert_book(&conn, 1, &snapshot).unwrap();
+        let (title, reverted_revision): (String, i64) = conn
+            .query_row("SELECT title, revision FROM books WHERE id = 1", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(title, "坊っちゃん");
+        assert!(reverted_revision > revision);
+        assert_eq!(ids(&conn, "SELECT tag_id FROM book_tags WHERE book_id = ?1", 1), [1]);
+        assert_eq!(ids(&conn, "SELECT volume FROM book_series WHERE book_id = ?1", 1), [2]);
+        assert_eq!(ids(&conn, "SELECT location_id FROM copies WHERE id = ?1", 1), [1]);
+        assert_eq!(ids(&conn, "SELECT COUNT(*) FROM book_notes WHERE book_id = ?1", 1), [1]);
+        // 後から増えた冊はそのまま残す
+        assert_eq!(ids(&conn, "SELECT id FROM copies WHERE book_id = ?1", 1), [1, 2]);
+    }
+}
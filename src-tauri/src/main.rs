@@ -1,9 +1,13 @@
 // Modularized main: database, models, and commands split into separate files.
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod collation;
 mod commands;
 mod db;
+mod error;
+mod history;
 mod models;
+mod normalize;
 
 use db::{setup_database, DbConnection};
 use std::sync::Mutex;
@@ -12,23 +16,110 @@ use tauri::Manager; // for app.manage
 fn main() {
     tauri::Builder::default()
         .setup(|app| {
-            let conn = setup_database(&app.handle()).expect("Failed to setup database");
+            let mut conn = setup_database(&app.handle()).expect("Failed to setup database");
+            if let Err(e) = commands::purge_expired_trash(&mut conn) {
+                eprintln!("ゴミ箱の整理に失敗しました: {}", e);
+            }
             app.manage(DbConnection(Mutex::new(conn)));
+            commands::start_backup_schedule(app.handle().clone());
             Ok(())
         })
+        .register_uri_scheme_protocol(commands::COVER_SCHEME, |_ctx, request| {
+            commands::cover_protocol(&request)
+        })
         .invoke_handler(tauri::generate_handler![
             commands::get_genres,
+            commands::get_genre_tree,
             commands::get_books_by_genre,
             commands::get_all_books,
+            commands::search_books,
             commands::add_book,
             commands::update_book,
+            commands::patch_book,
             commands::add_genre,
             commands::fetch_book_info_from_ndl,
             commands::fetch_book_info_from_google_books,
             commands::fetch_book_info_from_rakuten,
+            commands::refresh_book_metadata,
+            commands::apply_book_metadata,
+            commands::start_metadata_fill,
+            commands::cancel_metadata_fill,
             commands::delete_book,
+            commands::bulk_update_books,
             commands::get_book_count_by_genre,
+            commands::rename_genre,
+            commands::merge_genres,
+            commands::move_genre,
             commands::delete_genre,
+            commands::get_series_list,
+            commands::get_series_books,
+            commands::set_book_series,
+            commands::find_missing_volumes,
+            commands::get_tags,
+            commands::get_book_tags,
+            commands::add_tag,
+            commands::rename_tag,
+            commands::delete_tag,
+            commands::add_tags_to_books,
+            commands::remove_tags_from_books,
+            commands::start_reading,
+            commands::update_reading_progress,
+            commands::finish_reading,
+            commands::abandon_reading,
+            commands::delete_reading_session,
+            commands::get_current_reading,
+            commands::get_reading_sessions,
+            commands::get_read_counts,
+            commands::get_reading_stats,
+            commands::get_spending_summary,
+            commands::get_book_review,
+            commands::set_book_review,
+            commands::get_book_notes,
+            commands::add_book_note,
+            commands::update_book_note,
+            commands::delete_book_note,
+            commands::export_book_notes_markdown,
+            commands::lend_book,
+            commands::return_book,
+            commands::get_outstanding_loans,
+            commands::get_overdue_loans,
+            commands::get_loan_history,
+            commands::get_locations,
+            commands::add_location,
+            commands::rename_location,
+            commands::delete_location,
+            commands::move_books_to_location,
+            commands::get_location_books,
+            commands::find_books_by_isbn,
+            commands::stocktake,
+            commands::get_book_copies,
+            commands::add_copy,
+            commands::update_copy,
+            commands::record_purchase,
+            commands::delete_copy,
+            commands::move_copies_to_location,
+            commands::get_wishlist,
+            commands::add_wishlist_item,
+            commands::update_wishlist_item,
+            commands::delete_wishlist_item,
+            commands::purchase_wishlist_item,
+            commands::find_duplicates,
+            commands::merge_books,
+            commands::download_book_cover,
+            commands::set_book_cover_from_file,
+            commands::delete_book_cover,
+            commands::get_cover_book_ids,
+            commands::list_backups,
+            commands::backup_now,
+            commands::restore_backup,
+            commands::export_library_archive,
+            commands::import_library_archive,
+            commands::get_book_history,
+            commands::undo_last_change,
+            commands::restore_deleted_book,
+            commands::get_trash,
+            commands::restore_from_trash,
+            commands::empty_trash,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
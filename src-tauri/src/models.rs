@@ -1,34 +1,112 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Genre {
     pub id: i64,
     pub name: String,
+    pub parent_id: Option<i64>,
+    /// "YYYY-MM-DD HH:MM:SS"
+    pub created_at: String,
+    pub updated_at: String,
 }
 
+/// ジャンルツリーの1ノード。冊数は手元の冊数で、`total_book_count` は子孫ジャンルの分も含める。
 #[derive(Debug, Serialize, Deserialize)]
+pub struct GenreNode {
+    pub id: i64,
+    pub name: String,
+    pub parent_id: Option<i64>,
+    pub book_count: i64,
+    pub total_book_count: i64,
+    pub children: Vec<GenreNode>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Book {
     pub id: i64,
     pub isbn: Option<String>,
     pub title: String,
+    pub title_kana: Option<String>,
     pub author: Option<String>,
+    pub author_kana: Option<String>,
     pub publisher: Option<String>,
     pub price: Option<i64>,
     pub c_code: Option<String>,
     pub is_read: i64,
     pub genre_id: Option<i64>,
+    /// 0.5 刻みの 0.5〜5。未評価なら None
+    pub rating: Option<f64>,
+    /// 手元にある冊数
+    pub copy_count: i64,
+    /// 更新のたびに増える版番号。`patch_book` に渡すと、読み込んだ後に他で更新されていないか確かめられる。
+    pub revision: i64,
+    /// 登録日時 "YYYY-MM-DD HH:MM:SS"
+    pub created_at: String,
+    /// 最後に更新した日時。既読の切り替えやゴミ箱への移動も更新に数える。
+    pub updated_at: String,
+}
+
+/// 書籍一覧の並び順。書名・著者は読み（無ければ表記）を KANA 照合順序で比較し、
+/// 登録日時・更新日時は新しい順に並べる。
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookSort {
+    #[default]
+    Title,
+    Author,
+    CreatedAt,
+    UpdatedAt,
+}
+
+/// 一覧を登録日・更新日で絞り込む。日付は "YYYY-MM-DD" で、`from`・`to` の日を含む。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BookDateFilter {
+    pub field: BookDateField,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookDateField {
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl BookDateField {
+    pub fn column(self) -> &'static str {
+        match self {
+            BookDateField::CreatedAt => "created_at",
+            BookDateField::UpdatedAt => "updated_at",
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NewBook {
     pub title: String,
+    pub title_kana: Option<String>,
     pub genre_id: Option<i64>,
     pub isbn: Option<String>,
     pub author: Option<String>,
+    pub author_kana: Option<String>,
     pub publisher: Option<String>,
     pub price: Option<i64>,
     pub c_code: Option<String>,
     pub is_read: Option<i64>,
+    pub series: Option<String>,
+    pub volume: Option<i64>,
+    /// 最初の1冊を置く場所
+    pub location_id: Option<i64>,
+    /// true なら同じ本らしき登録があっても登録する
+    pub allow_duplicate: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -36,17 +114,764 @@ pub struct UpdateBook {
     pub id: i64,
     pub isbn: Option<String>,
     pub title: String,
+    pub title_kana: Option<String>,
     pub author: Option<String>,
+    pub author_kana: Option<String>,
     pub publisher: Option<String>,
     pub price: Option<i64>,
     pub c_code: Option<String>,
     pub is_read: i64,
     pub genre_id: Option<i64>,
+    /// 読み込んだときの `Book::revision`。渡すと、その後に他で更新されていれば保存しない。
+    pub revision: Option<i64>,
+}
+
+/// 本の一部の項目だけを変える。省略した項目は変えず、null を渡すとその値を外す
+/// （`title` と `is_read` は外せないので、null は省略と同じ扱い）。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BookPatch {
+    pub id: i64,
+    /// 読み込んだときの `Book::revision`。その後に他で更新されていれば保存しない。
+    pub revision: i64,
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub title_kana: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub isbn: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub author: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub author_kana: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub price: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub c_code: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub genre_id: Option<Option<i64>>,
+    pub is_read: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BookInfoFromApi {
+    pub title: String,
+    pub title_kana: Option<String>,
+    pub author: String,
+    pub author_kana: Option<String>,
+    pub publisher: String,
+    pub series: Option<String>,
+    pub volume: Option<i64>,
+    pub isbn: Option<String>,
+    /// 書影の URL。download_book_cover に渡すと保存できる。
+    pub cover_url: Option<String>,
+}
+
+/// プロバイダの検索で見つかった、シリーズのうち未所持の巻
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VolumeCandidate {
+    pub isbn: String,
     pub title: String,
     pub author: String,
     pub publisher: String,
+    pub volume: i64,
+    /// 所持している最新巻より後の巻なら true、途中の抜けなら false
+    pub is_newer: bool,
+    pub source: String,
+}
+
+/// シリーズごとの所持状況。`missing_volumes` は 1 から最大所持巻までの抜け。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SeriesSummary {
+    pub id: i64,
+    pub title: String,
+    pub title_kana: Option<String>,
+    pub book_count: i64,
+    pub owned_volumes: Vec<i64>,
+    pub missing_volumes: Vec<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SeriesVolume {
+    pub volume: Option<i64>,
+    pub book: Book,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadingStatus {
+    Reading,
+    Finished,
+    Abandoned,
+}
+
+impl ReadingStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReadingStatus::Reading => "reading",
+            ReadingStatus::Finished => "finished",
+            ReadingStatus::Abandoned => "abandoned",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "reading" => Some(ReadingStatus::Reading),
+            "finished" => Some(ReadingStatus::Finished),
+            "abandoned" => Some(ReadingStatus::Abandoned),
+            _ => None,
+        }
+    }
+}
+
+/// 1回分の読書記録。日付は "YYYY-MM-DD"。既読フラグから作られた記録は日付を持たない。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadingSession {
+    pub id: i64,
+    pub book_id: i64,
+    pub status: ReadingStatus,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub current_page: Option<i64>,
+    pub total_pages: Option<i64>,
+}
+
+/// 読書中の本とその記録
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CurrentReading {
+    pub session: ReadingSession,
+    pub book: Book,
+}
+
+/// 本ごとの読了回数（再読を含む）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BookReadCount {
+    pub book_id: i64,
+    pub read_count: i64,
+}
+
+/// 期間ごとの読了冊数と読んだページ数（period は "YYYY-MM" または "YYYY"）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PeriodStat {
+    pub period: String,
+    pub finished_count: i64,
+    pub pages_read: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RankedCount {
+    pub name: String,
+    pub count: i64,
+}
+
+/// 読書統計。月別・年別の系列は期間内のすべての月・年を 0 件も含めて並べる。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadingStats {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub finished_count: i64,
+    pub pages_read: i64,
+    pub by_month: Vec<PeriodStat>,
+    pub by_year: Vec<PeriodStat>,
+    pub top_authors: Vec<RankedCount>,
+    pub top_publishers: Vec<RankedCount>,
+    pub top_genres: Vec<RankedCount>,
+    /// 開始日と読了日が両方分かる記録の平均日数
+    pub average_days_to_finish: Option<f64>,
+    /// 未読の冊数と定価の合計（期間に関係なく現在の値）
+    pub backlog_count: i64,
+    pub backlog_price: i64,
+}
+
+/// 本の評価とレビュー
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BookReview {
+    pub book_id: i64,
+    pub rating: Option<f64>,
+    pub review: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteKind {
+    /// 読みながら書いたメモ
+    Note,
+    /// 本文からの引用
+    Quote,
+}
+
+impl NoteKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NoteKind::Note => "note",
+            NoteKind::Quote => "quote",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "note" => Some(NoteKind::Note),
+            "quote" => Some(NoteKind::Quote),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BookNote {
+    pub id: i64,
+    pub book_id: i64,
+    pub kind: NoteKind,
+    pub body: String,
+    pub page: Option<i64>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewBookNote {
+    pub book_id: i64,
+    pub kind: NoteKind,
+    pub body: String,
+    pub page: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateBookNote {
+    pub id: i64,
+    pub kind: NoteKind,
+    pub body: String,
+    pub page: Option<i64>,
+}
+
+/// 貸出記録。日付は "YYYY-MM-DD"、返却前は `returned_at` が None。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Loan {
+    pub id: i64,
+    pub book_id: i64,
+    pub copy_id: i64,
+    pub borrower: String,
+    pub lent_at: String,
+    pub due_at: Option<String>,
+    pub returned_at: Option<String>,
+}
+
+/// 貸出中の本。返却期限を過ぎていれば `is_overdue` が true。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutstandingLoan {
+    pub loan: Loan,
+    pub book: Book,
+    pub is_overdue: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationKind {
+    Building,
+    Room,
+    Shelf,
+}
+
+impl LocationKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LocationKind::Building => "building",
+            LocationKind::Room => "room",
+            LocationKind::Shelf => "shelf",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "building" => Some(LocationKind::Building),
+            "room" => Some(LocationKind::Room),
+            "shelf" => Some(LocationKind::Shelf),
+            _ => None,
+        }
+    }
+
+    /// この種類の場所を置ける親の種類。建物は最上位なので None。
+    pub fn parent_kind(self) -> Option<Self> {
+        match self {
+            LocationKind::Building => None,
+            LocationKind::Room => Some(LocationKind::Building),
+            LocationKind::Shelf => Some(LocationKind::Room),
+        }
+    }
+}
+
+/// 置き場所。`path` は "本社 > 3F 資料室 > 棚A" のような表示用の名前。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Location {
+    pub id: i64,
+    pub name: String,
+    pub kind: LocationKind,
+    pub parent_id: Option<i64>,
+    pub path: String,
+}
+
+/// 1冊ごとの置き場所
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CopyLocation {
+    pub book: Book,
+    pub copy: Copy,
+    pub location: Option<Location>,
+}
+
+/// 棚卸しの結果
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StocktakeResult {
+    pub location_id: i64,
+    /// 棚にあるはずで、読み取れた本
+    pub found: Vec<CopyLocation>,
+    /// 棚にあるはずなのに、読み取れなかった本
+    pub missing: Vec<CopyLocation>,
+    /// 読み取れたが、別の場所（または場所未設定）で登録されている本
+    pub misplaced: Vec<CopyLocation>,
+    /// 蔵書に無い ISBN
+    pub unknown_isbns: Vec<String>,
+}
+
+/// 手元にある1冊。同じ版を複数持っている場合は1つの本に複数ぶら下がる。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Copy {
+    pub id: i64,
+    pub book_id: i64,
+    /// 状態（"新品" "良い" "書き込みあり" など）
+    pub condition: Option<String>,
+    pub acquired_at: Option<String>,
+    /// 実際に払った金額。`Book::price` は JAN コードの定価。
+    pub purchase_price: Option<i64>,
+    pub store: Option<String>,
+    pub acquisition: Option<AcquisitionKind>,
+    pub location_id: Option<i64>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewCopy {
+    pub book_id: i64,
+    pub condition: Option<String>,
+    /// 省略すると今日の日付になる
+    pub acquired_at: Option<String>,
+    pub purchase_price: Option<i64>,
+    pub store: Option<String>,
+    pub acquisition: Option<AcquisitionKind>,
+    pub location_id: Option<i64>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateCopy {
+    pub id: i64,
+    pub condition: Option<String>,
+    pub acquired_at: Option<String>,
+    pub purchase_price: Option<i64>,
+    pub store: Option<String>,
+    pub acquisition: Option<AcquisitionKind>,
+    pub location_id: Option<i64>,
+    pub notes: Option<String>,
+}
+
+/// 入手の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AcquisitionKind {
+    New,
+    Used,
+    Gift,
+}
+
+impl AcquisitionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AcquisitionKind::New => "new",
+            AcquisitionKind::Used => "used",
+            AcquisitionKind::Gift => "gift",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "new" => Some(AcquisitionKind::New),
+            "used" => Some(AcquisitionKind::Used),
+            "gift" => Some(AcquisitionKind::Gift),
+            _ => None,
+        }
+    }
+}
+
+/// 1冊の購入記録。状態や置き場所は変えずに、購入に関する項目だけを書き換える。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Purchase {
+    pub copy_id: i64,
+    /// 省略すると今日の日付になる
+    pub acquired_at: Option<String>,
+    pub purchase_price: Option<i64>,
+    pub store: Option<String>,
+    pub acquisition: AcquisitionKind,
+}
+
+/// 期間ごとの支出（period は "YYYY-MM" または "YYYY"）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PeriodSpending {
+    pub period: String,
+    pub spent: i64,
+    pub copy_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AcquisitionSpending {
+    /// None は入手の種類が記録されていない冊
+    pub acquisition: Option<AcquisitionKind>,
+    pub spent: i64,
+    pub copy_count: i64,
+}
+
+/// 入手日が期間内の冊の支出集計
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpendingSummary {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub total_spent: i64,
+    pub copy_count: i64,
+    pub by_month: Vec<PeriodSpending>,
+    pub by_year: Vec<PeriodSpending>,
+    pub by_acquisition: Vec<AcquisitionSpending>,
+    /// 購入した冊数の多い書店
+    pub top_stores: Vec<RankedCount>,
+}
+
+/// 欲しいものリストの1件。`priority` は 1（最優先）〜5。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WishlistItem {
+    pub id: i64,
+    pub isbn: Option<String>,
+    pub title: String,
+    pub title_kana: Option<String>,
+    pub author: Option<String>,
+    pub author_kana: Option<String>,
+    pub publisher: Option<String>,
+    /// 定価
+    pub price: Option<i64>,
+    pub c_code: Option<String>,
+    pub series: Option<String>,
+    pub volume: Option<i64>,
+    pub priority: i64,
+    /// この値段以下なら買う、という目安
+    pub target_price: Option<i64>,
+    pub notes: Option<String>,
+    pub added_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewWishlistItem {
+    pub isbn: Option<String>,
+    pub title: String,
+    pub title_kana: Option<String>,
+    pub author: Option<String>,
+    pub author_kana: Option<String>,
+    pub publisher: Option<String>,
+    pub price: Option<i64>,
+    pub c_code: Option<String>,
+    pub series: Option<String>,
+    pub volume: Option<i64>,
+    /// 省略すると 3
+    pub priority: Option<i64>,
+    pub target_price: Option<i64>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateWishlistItem {
+    pub id: i64,
+    pub isbn: Option<String>,
+    pub title: String,
+    pub title_kana: Option<String>,
+    pub author: Option<String>,
+    pub author_kana: Option<String>,
+    pub publisher: Option<String>,
+    pub price: Option<i64>,
+    pub c_code: Option<String>,
+    pub series: Option<String>,
+    pub volume: Option<i64>,
+    pub priority: i64,
+    pub target_price: Option<i64>,
+    pub notes: Option<String>,
+}
+
+/// 欲しいものリストの本を買ったときに、蔵書へ移す内容
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PurchaseWishlistItem {
+    pub id: i64,
+    pub genre_id: Option<i64>,
+    pub location_id: Option<i64>,
+    /// 省略すると今日の日付になる
+    pub acquired_at: Option<String>,
+    pub purchase_price: Option<i64>,
+    pub store: Option<String>,
+    pub acquisition: Option<AcquisitionKind>,
+}
+
+/// 重複とみなした理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    /// ISBN が同じ（ハイフンや ISBN-10/13 の違いは無視する）
+    Isbn,
+    /// 著者が同じで、書名がほぼ同じ（巻数は一致が必要）
+    TitleAuthor,
+}
+
+/// 登録しようとした本と重複していそうな既存の本
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicateMatch {
+    pub book: Book,
+    pub reason: DuplicateReason,
+}
+
+/// 蔵書の中で重複していそうな本のまとまり
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub reason: DuplicateReason,
+    pub books: Vec<Book>,
+}
+
+/// 統合するときに、どちらの値を残すか選べる項目
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookField {
+    Isbn,
+    Title,
+    TitleKana,
+    Author,
+    AuthorKana,
+    Publisher,
+    Price,
+    CCode,
+    GenreId,
+    Rating,
+    Review,
+    Series,
+}
+
+impl BookField {
+    /// books の列名。シリーズは book_series で持つので None。
+    pub fn column(self) -> Option<&'static str> {
+        match self {
+            BookField::Isbn => Some("isbn"),
+            BookField::Title => Some("title"),
+            BookField::TitleKana => Some("title_kana"),
+            BookField::Author => Some("author"),
+            BookField::AuthorKana => Some("author_kana"),
+            BookField::Publisher => Some("publisher"),
+            BookField::Price => Some("price"),
+            BookField::CCode => Some("c_code"),
+            BookField::GenreId => Some("genre_id"),
+            BookField::Rating => Some("rating"),
+            BookField::Review => Some("review"),
+            BookField::Series => None,
+        }
+    }
+}
+
+/// `source_id` の本を `target_id` の本に統合する。`take_from_source` に挙げた項目は
+/// source の値を使い、それ以外は target の値を残す（target が空なら source の値で埋める）。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MergeBooks {
+    pub target_id: i64,
+    pub source_id: i64,
+    #[serde(default)]
+    pub take_from_source: Vec<BookField>,
+}
+
+/// 選択した本にまとめて適用する変更。省略した項目は変えず、
+/// `genre_id`・`location_id`・`publisher` に null を渡すとその値を外す。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkBookPatch {
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub genre_id: Option<Option<i64>>,
+    pub is_read: Option<bool>,
+    #[serde(default)]
+    pub add_tag_ids: Vec<i64>,
+    #[serde(default)]
+    pub remove_tag_ids: Vec<i64>,
+    /// 本のすべての冊をこの場所に移す
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub location_id: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<Option<String>>,
+}
+
+/// 項目が無ければ None（`#[serde(default)]` による）、null なら Some(None) にする。
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// ISBN で書誌情報を引く先
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataProvider {
+    Ndl,
+    Google,
+    Rakuten,
+}
+
+/// 書誌情報の取得設定。`priority` の順に問い合わせ、書名・著者・出版社が揃ったところで止める。
+/// `priority` が空なら NDL・Google Books・楽天ブックスの順。楽天はアプリケーションIDが無ければ飛ばす。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderSettings {
+    #[serde(default)]
+    pub priority: Vec<MetadataProvider>,
+    pub google_api_key: Option<String>,
+    pub rakuten_application_id: Option<String>,
+}
+
+/// 取り直した書誌情報のうち、登録内容と異なる項目
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetadataChange {
+    pub field: BookField,
+    pub current: Option<String>,
+    pub fetched: String,
+}
+
+/// 1冊分の書誌情報の取り直し結果。取得できなければ `error` に理由が入る。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetadataRefresh {
+    pub book_id: i64,
+    pub title: String,
+    /// 取り直したときの `Book::revision`。`apply_book_metadata` にそのまま渡す。
+    pub revision: i64,
+    pub changes: Vec<MetadataChange>,
+    pub error: Option<String>,
+}
+
+/// 取り直した書誌情報のうち、採用する項目と値
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AcceptedMetadata {
+    pub book_id: i64,
+    pub revision: i64,
+    pub fields: HashMap<BookField, String>,
+}
+
+/// 空欄の補完の進み具合。1冊終わるごとに `metadata-fill-progress` で送る。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataFillProgress {
+    pub processed: usize,
+    pub total: usize,
+    pub book_id: i64,
+    pub title: String,
+    /// 埋めた項目
+    pub filled: Vec<BookField>,
+    pub error: Option<String>,
+}
+
+/// 空欄の補完の結果。終わったとき（中止したときも）に `metadata-fill-finished` で送る。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataFillSummary {
+    pub total: usize,
+    pub processed: usize,
+    pub updated: usize,
+    pub failed: usize,
+    pub cancelled: bool,
+}
+
+/// `backups/` にあるバックアップ
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub file_name: String,
+    /// "YYYY-MM-DD HH:MM:SS"（ファイル名の日時）
+    pub created_at: String,
+    pub size: u64,
+    /// バックアップのスキーマバージョン。読めないファイルは None。
+    pub schema_version: Option<i64>,
+}
+
+/// ライブラリのアーカイブ（zip）に入れる manifest.json
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    /// 常に "bibly-archive"
+    pub format: String,
+    pub format_version: i64,
+    pub app_version: String,
+    pub schema_version: i64,
+    pub exported_at: String,
+    pub book_count: i64,
+    pub cover_count: i64,
+}
+
+/// アーカイブの取り込み方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// 今のライブラリをアーカイブの内容で置き換える
+    Replace,
+    /// 今のライブラリに無い本だけを追加する
+    Merge,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub manifest: ArchiveManifest,
+    pub books_added: i64,
+    /// 取り込み先に同じ本があったので追加しなかった本の数（merge のとき）
+    pub books_skipped: i64,
+    pub covers_imported: i64,
+    /// エクスポート元の設定（localStorage の内容）。フロントエンドで書き戻す。
+    pub settings: HashMap<String, String>,
+}
+
+/// 履歴に残る変更の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryAction {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl HistoryAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HistoryAction::Insert => "insert",
+            HistoryAction::Update => "update",
+            HistoryAction::Delete => "delete",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "insert" => Some(HistoryAction::Insert),
+            "update" => Some(HistoryAction::Update),
+            "delete" => Some(HistoryAction::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// 本の変更履歴の1件。`before` / `after` は変更前後のスナップショットで、
+/// テーブル名ごとに行の配列を持つ（`{"books": [{...}], "copies": [...], ...}`）。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BookHistoryEntry {
+    pub id: i64,
+    pub book_id: i64,
+    pub action: HistoryAction,
+    pub title: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub changed_at: String,
+    /// 取り消した日時。取り消していなければ None。
+    pub undone_at: Option<String>,
+}
+
+/// ゴミ箱にある本
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashedBook {
+    pub book: Book,
+    pub deleted_at: String,
+    /// この日を過ぎると起動時に完全に削除される
+    pub purge_on: String,
 }
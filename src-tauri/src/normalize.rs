@@ -0,0 +1,344 @@
+// Normalization of author names, kana readings and series volumes coming from the book info APIs.
+//
+// NDL returns "夏目, 漱石, 1867-1916", Rakuten "夏目漱石", Google Books a list of names.
+// Everything is unified here so that the same author groups and sorts together.
+use unicode_normalization::UnicodeNormalization;
+
+/// 複数著者を連結するときの区切り
+pub const AUTHOR_SEPARATOR: &str = " / ";
+
+/// 役割表記（"夏目漱石 著" の "著" など）として末尾から取り除く語
+const ROLE_SUFFIXES: &[&str] = &[
+    "編著", "原作", "作画", "監修", "監訳", "著", "作", "訳", "編", "絵", "文", "画",
+];
+
+/// 著者文字列を正規化する。
+/// 生没年を取り除き、全角/半角を統一し、"姓, 名" 形式を表示用の並びに直す。
+pub fn normalize_author(raw: &str) -> String {
+    let unified = unify_width(raw);
+    split_authors(&unified)
+        .map(normalize_author_name)
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>()
+        .join(AUTHOR_SEPARATOR)
+}
+
+/// 著者の読みを正規化する。カタカナに揃え、姓→名の順のまま空白で区切る。
+pub fn normalize_author_reading(raw: &str) -> String {
+    let unified = to_katakana(&unify_width(raw));
+    split_authors(&unified)
+        .map(|name| name_parts(name).join(" "))
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>()
+        .join(AUTHOR_SEPARATOR)
+}
+
+/// 書名の読みを正規化する。カタカナに揃え、空白を1つにまとめる。
+pub fn normalize_title_reading(raw: &str) -> String {
+    to_katakana(&unify_width(raw))
+}
+
+/// かなだけで書かれた文字列なら、そのまま読みとして使えるカタカナを返す。
+pub fn reading_from_kana(text: &str) -> Option<String> {
+    let text = unify_width(text);
+    let has_kana = text.chars().any(is_kana);
+    let only_kana = text
+        .chars()
+        .all(|c| is_kana(c) || c.is_whitespace() || c == '/' || c == ',');
+    if has_kana && only_kana {
+        Some(to_katakana(&text))
+    } else {
+        None
+    }
+}
+
+/// 書誌情報から (シリーズ名, 巻数) を決める。
+/// 巻数が分かる場合は巻数を除いた書名をシリーズとし、分からなければ叢書名（文庫・レーベル名）を使う。
+pub fn resolve_series(
+    title: &str,
+    series_title: Option<&str>,
+    volume: Option<&str>,
+) -> (Option<String>, Option<i64>) {
+    let (base_title, title_volume) = split_volume(title);
+    if let Some(volume) = volume.and_then(parse_volume).or(title_volume) {
+        return (Some(base_title), Some(volume));
+    }
+    let series = series_title
+        .and_then(|s| s.split(';').next())
+        .map(unify_width)
+        .filter(|s| !s.is_empty());
+    (series, None)
+}
+
+/// 書名末尾の巻数を取り出す（"ONE PIECE 107" → ("ONE PIECE", Some(107))）。
+/// 空白・"第"・括弧などで区切られているか "巻" が付いている3桁までの数字だけを巻数とみなし、
+/// "ブレードランナー2049" のように書名に続く数字は書名の一部として残す。
+pub fn split_volume(title: &str) -> (String, Option<i64>) {
+    let text = unify_width(title);
+    let body = text.trim_end_matches([')', '巻', ' ']);
+    let has_suffix = body.len() < text.len();
+    let digits_start = body
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_ascii_digit())
+        .last()
+        .map(|(i, _)| i);
+    let Some(start) = digits_start else {
+        return (text, None);
+    };
+    let Ok(volume) = body[start..].parse::<i64>() else {
+        return (text, None);
+    };
+    let mut base = body[..start].trim_end().trim_end_matches(['第', '(', '#']).trim_end();
+    let mut has_marker = base.len() < start;
+    for prefix in ["vol.", "Vol.", "VOL.", "vol", "Vol", "VOL"] {
+        if let Some(rest) = base.strip_suffix(prefix) {
+            base = rest.trim_end();
+            has_marker = true;
+            break;
+        }
+    }
+    if !(has_suffix || has_marker) || volume >= 1000 {
+        return (text, None);
+    }
+    let base = base.trim_end_matches([' ', ':', '-']);
+    if base.is_empty() {
+        // "1984" のように数字だけの書名は巻数とみなさない
+        return (text, None);
+    }
+    (base.to_string(), Some(volume))
+}
+
+/// "巻1" "第3巻" のような巻次表記から最初の数字を取り出す。
+pub fn parse_volume(raw: &str) -> Option<i64> {
+    let text = unify_width(raw);
+    let digits: String = text
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+/// ISBN をハイフン無しの13桁に揃える。10桁なら978を付けてチェックディジットを計算し直す。
+/// 978/979 で始まらない13桁（価格の2段目バーコードなど）やチェックディジットの合わないものは None。
+pub fn normalize_isbn(raw: &str) -> Option<String> {
+    let digits: String = unify_width(raw)
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == 'X' || *c == 'x')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    match digits.len() {
+        13 if (digits.starts_with("978") || digits.starts_with("979"))
+            && digits.chars().all(|c| c.is_ascii_digit())
+            && ean13_check_digit(&digits[..12]) == digits.chars().nth(12) =>
+        {
+            Some(digits)
+        }
+        10 if digits[..9].chars().all(|c| c.is_ascii_digit())
+            && isbn10_check_digit(&digits[..9]) == digits.chars().nth(9) =>
+        {
+            let body = format!("978{}", &digits[..9]);
+            let check = ean13_check_digit(&body)?;
+            Some(format!("{}{}", body, check))
+        }
+        _ => None,
+    }
+}
+
+/// 12桁の本体から EAN-13 のチェックディジットを計算する。
+fn ean13_check_digit(body: &str) -> Option<char> {
+    let sum: u32 = body
+        .chars()
+        .enumerate()
+        .map(|(i, c)| c.to_digit(10).unwrap_or(0) * if i % 2 == 0 { 1 } else { 3 })
+        .sum();
+    char::from_digit((10 - sum % 10) % 10, 10)
+}
+
+/// 9桁の本体から ISBN-10 のチェックディジット（0〜9 か X）を計算する。
+fn isbn10_check_digit(body: &str) -> Option<char> {
+    let sum: u32 = body
+        .chars()
+        .enumerate()
+        .map(|(i, c)| c.to_digit(10).unwrap_or(0) * (10 - i as u32))
+        .sum();
+    match (11 - sum % 11) % 11 {
+        10 => Some('X'),
+        n => char::from_digit(n, 10),
+    }
+}
+
+/// 978 で始まる13桁の ISBN を10桁に戻す。979 で始まるものは10桁が無いので None。
+pub fn isbn10_from_isbn13(isbn13: &str) -> Option<String> {
+    let body = isbn13.strip_prefix("978").filter(|_| isbn13.len() == 13)?.get(..9)?;
+    Some(format!("{}{}", body, isbn10_check_digit(body)?))
+}
+
+/// 全角英数・記号を半角に、半角カナを全角に揃え、空白を1つにまとめる。
+pub fn unify_width(s: &str) -> String {
+    let nfkc: String = s.nfkc().collect();
+    nfkc.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// ひらがなをカタカナに変換する。
+pub fn to_katakana(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{3041}'..='\u{3096}' | '\u{309D}'..='\u{309E}' => {
+                char::from_u32(c as u32 + 0x60).unwrap_or(c)
+            }
+            _ => c,
+        })
+        .collect()
+}
+
+pub fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{309F}' | '\u{30A0}'..='\u{30FF}')
+}
+
+fn is_cjk(c: char) -> bool {
+    is_kana(c)
+        || matches!(
+            c,
+            '\u{3005}' | '\u{3006}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}'
+        )
+}
+
+fn is_katakana_name(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| matches!(c, '\u{30A0}'..='\u{30FF}' | '='))
+}
+
+fn split_authors(s: &str) -> impl Iterator<Item = &str> {
+    s.split(['/', ';']).map(str::trim)
+}
+
+/// "夏目, 漱石, 1867-1916" を ["夏目", "漱石"] に分解する。
+fn name_parts(name: &str) -> Vec<String> {
+    name.split(',')
+        .map(|part| strip_role_suffix(part.trim()))
+        .filter(|part| !part.is_empty() && !is_life_dates(part))
+        .map(join_cjk_words)
+        .collect()
+}
+
+fn normalize_author_name(name: &str) -> String {
+    let parts = name_parts(name);
+    match parts.as_slice() {
+        [] => String::new(),
+        [single] => single.clone(),
+        [family, given @ ..] => {
+            let given = given.join(" ");
+            if is_katakana_name(family) && is_katakana_name(&given) && given.contains(['・', '=']) {
+                // 翻訳書の著者名（"ドイル, アーサー・コナン"）は "名・姓" の表記に揃える。
+                // "ヤマザキ, マリ" のように名が一語なら日本人名かもしれないので並べ替えない
+                format!("{}・{}", given, family)
+            } else if family.chars().any(is_cjk) {
+                format!("{}{}", family, given)
+            } else {
+                format!("{} {}", given, family)
+            }
+        }
+    }
+}
+
+fn strip_role_suffix(part: &str) -> &str {
+    for suffix in ROLE_SUFFIXES {
+        if let Some(rest) = part.strip_suffix(suffix) {
+            if rest.ends_with(char::is_whitespace) {
+                return rest.trim_end();
+            }
+        }
+    }
+    part
+}
+
+fn is_life_dates(part: &str) -> bool {
+    if part == "生没年不詳" {
+        return true;
+    }
+    part.chars().any(|c| c.is_ascii_digit())
+        && part
+            .chars()
+            .all(|c| c.is_ascii_digit() || c.is_whitespace() || "-?.年頃生没ca".contains(c))
+}
+
+/// 漢字・かなの間に挟まった空白を取り除く（"夏目 漱石" → "夏目漱石"）。
+fn join_cjk_words(part: &str) -> String {
+    let chars: Vec<char> = part.chars().collect();
+    let mut out = String::with_capacity(part.len());
+    for (i, &c) in chars.iter().enumerate() {
+        if c == ' ' && i > 0 && i + 1 < chars.len() && is_cjk(chars[i - 1]) && is_cjk(chars[i + 1]) {
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_author_reorders_translated_names() {
+        assert_eq!(normalize_author("ドイル, アーサー・コナン"), "アーサー・コナン・ドイル");
+        assert_eq!(normalize_author("サン=テグジュペリ, アントワーヌ・ド"), "アントワーヌ・ド・サン=テグジュペリ");
+        assert_eq!(normalize_author("Doyle, Arthur Conan"), "Arthur Conan Doyle");
+    }
+
+    #[test]
+    fn normalize_author_keeps_order_of_single_word_katakana_names() {
+        assert_eq!(normalize_author("ヤマザキ, マリ, 1967-"), "ヤマザキマリ");
+        assert_eq!(normalize_author("夏目, 漱石"), "夏目漱石");
+    }
+
+    #[test]
+    fn normalize_author_strips_life_dates_and_roles() {
+        assert_eq!(normalize_author("夏目, 漱石, 1867-1916"), "夏目漱石");
+        assert_eq!(normalize_author("紫式部, 生没年不詳"), "紫式部");
+        assert_eq!(normalize_author("夏目 漱石 著"), "夏目漱石");
+        assert_eq!(normalize_author("芥川, 龍之介, 1892-1927 / 太宰, 治"), "芥川龍之介 / 太宰治");
+    }
+
+    #[test]
+    fn split_volume_takes_separated_numbers() {
+        assert_eq!(split_volume("ONE PIECE 107"), ("ONE PIECE".into(), Some(107)));
+        assert_eq!(split_volume("進撃の巨人(1)"), ("進撃の巨人".into(), Some(1)));
+        assert_eq!(split_volume("ベルセルク 第41巻"), ("ベルセルク".into(), Some(41)));
+        assert_eq!(split_volume("よつばと! Vol.15"), ("よつばと!".into(), Some(15)));
+        assert_eq!(split_volume("ＳＬＡＭ ＤＵＮＫ　３"), ("SLAM DUNK".into(), Some(3)));
+    }
+
+    #[test]
+    fn split_volume_keeps_numbers_that_belong_to_the_title() {
+        assert_eq!(split_volume("ブレードランナー2049"), ("ブレードランナー2049".into(), None));
+        assert_eq!(split_volume("1984"), ("1984".into(), None));
+        assert_eq!(split_volume("2001年宇宙の旅"), ("2001年宇宙の旅".into(), None));
+        assert_eq!(split_volume("宇宙の旅 2001"), ("宇宙の旅 2001".into(), None));
+    }
+
+    #[test]
+    fn normalize_isbn_converts_isbn10() {
+        assert_eq!(normalize_isbn("4-10-101001-3").as_deref(), Some("9784101010014"));
+        assert_eq!(normalize_isbn("０８０４４２９５７ｘ").as_deref(), Some("9780804429573"));
+        assert_eq!(normalize_isbn("978-4-10-101001-4").as_deref(), Some("9784101010014"));
+    }
+
+    #[test]
+    fn normalize_isbn_rejects_invalid_codes() {
+        // 価格の2段目バーコード
+        assert_eq!(normalize_isbn("1923093006000"), None);
+        assert_eq!(normalize_isbn("9784101010015"), None);
+        assert_eq!(normalize_isbn("4101010014"), None);
+        assert_eq!(normalize_isbn("X101010013"), None);
+        assert_eq!(normalize_isbn("12345"), None);
+    }
+
+    #[test]
+    fn isbn10_round_trips() {
+        assert_eq!(isbn10_from_isbn13("9784101010014").as_deref(), Some("4101010013"));
+        assert_eq!(isbn10_from_isbn13("9780804429573").as_deref(), Some("080442957X"));
+        assert_eq!(isbn10_from_isbn13("9791234567896"), None);
+    }
+}
@@ -0,0 +1,41 @@
+import { invoke } from '@tauri-apps/api/core';
+import type { ArchiveManifest, ImportMode, ImportResult } from './types';
+
+// API キーなど、頼まれない限りアーカイブに入れない設定
+const SECRET_SETTING_KEYS = ['googleBooksApiKey', 'rakutenApplicationId'];
+
+// localStorage に置いている設定を取り出す（アーカイブに入れる用）。
+// API キーは平文で書き出されるので、includeSecrets のときだけ含める。
+export function collectSettings(includeSecrets = false): Record<string, string> {
+  const settings: Record<string, string> = {};
+  for (let i = 0; i < localStorage.length; i++) {
+    const key = localStorage.key(i);
+    if (key !== null && (includeSecrets || !SECRET_SETTING_KEYS.includes(key))) {
+      settings[key] = localStorage.getItem(key) ?? '';
+    }
+  }
+  return settings;
+}
+
+export function applySettings(settings: Record<string, string>) {
+  for (const [key, value] of Object.entries(settings)) {
+    localStorage.setItem(key, value);
+  }
+}
+
+export function exportLibraryArchive(path: string, includeSecrets = false): Promise<ArchiveManifest> {
+  return invoke<ArchiveManifest>('export_library_archive', {
+    path,
+    settings: collectSettings(includeSecrets),
+  });
+}
+
+// 置き換えで取り込んだときだけ、アーカイブの設定を書き戻す。反映には再読み込みが要る。
+// 統合ではこちらの設定をそのまま使う。
+export async function importLibraryArchive(path: string, mode: ImportMode): Promise<ImportResult> {
+  const result = await invoke<ImportResult>('import_library_archive', { path, mode });
+  if (mode === 'replace') {
+    applySettings(result.settings);
+  }
+  return result;
+}
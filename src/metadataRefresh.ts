@@ -0,0 +1,66 @@
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type {
+  AcceptedMetadata,
+  Book,
+  MetadataFillProgress,
+  MetadataFillSummary,
+  MetadataProvider,
+  MetadataRefresh,
+  ProviderSettings,
+} from './types';
+
+const GOOGLE_API_KEY_STORAGE = 'googleBooksApiKey';
+const RAKUTEN_APP_ID_STORAGE = 'rakutenApplicationId';
+const API_PRIORITY_STORAGE = 'bookInfoApiPriority';
+const ALL_API_PROVIDERS: MetadataProvider[] = ['ndl', 'google', 'rakuten'];
+
+// 設定画面で選んだ取得順と API キーを、バックエンドに渡す形にする
+export function providerSettings(): ProviderSettings {
+  let priority: MetadataProvider[] = [];
+  try {
+    const parsed = JSON.parse(localStorage.getItem(API_PRIORITY_STORAGE) || '[]');
+    if (Array.isArray(parsed)) {
+      priority = parsed.filter((value): value is MetadataProvider => ALL_API_PROVIDERS.includes(value));
+    }
+  } catch {
+    // 壊れた設定は既定の順にする
+  }
+  return {
+    priority,
+    google_api_key: (localStorage.getItem(GOOGLE_API_KEY_STORAGE) || '').trim() || null,
+    rakuten_application_id: (localStorage.getItem(RAKUTEN_APP_ID_STORAGE) || '').trim() || null,
+  };
+}
+
+export function refreshBookMetadata(bookIds: number[]): Promise<MetadataRefresh[]> {
+  return invoke<MetadataRefresh[]>('refresh_book_metadata', { bookIds, providers: providerSettings() });
+}
+
+export function applyBookMetadata(updates: AcceptedMetadata[]): Promise<Book[]> {
+  return invoke<Book[]>('apply_book_metadata', { updates });
+}
+
+// 空欄の補完を始め、対象の冊数を返す。終わるか中止すると onFinished の後に購読を外す。
+export async function startMetadataFill(
+  onProgress: (progress: MetadataFillProgress) => void,
+  onFinished: (summary: MetadataFillSummary) => void,
+): Promise<number> {
+  const unlisteners: UnlistenFn[] = [];
+  const unlistenAll = () => unlisteners.forEach(unlisten => unlisten());
+  unlisteners.push(await listen<MetadataFillProgress>('metadata-fill-progress', event => onProgress(event.payload)));
+  unlisteners.push(await listen<MetadataFillSummary>('metadata-fill-finished', event => {
+    unlistenAll();
+    onFinished(event.payload);
+  }));
+  try {
+    return await invoke<number>('start_metadata_fill', { providers: providerSettings() });
+  } catch (e) {
+    unlistenAll();
+    throw e;
+  }
+}
+
+export function cancelMetadataFill(): Promise<void> {
+  return invoke('cancel_metadata_fill');
+}
@@ -5,44 +5,114 @@ export interface Book {
   id: number;
   isbn?: string;
   title: string;
+  title_kana?: string | null; // 書名の読み（五十音順ソート用）
   author?: string;
+  author_kana?: string | null; // 著者の読み（五十音順ソート用）
   publisher?: string;
   price?: number;
   c_code?: string;
   is_read: number;
   genre_id?: number | null; // 'null' を追加
+  rating?: number | null; // 0.5刻みの0.5〜5
+  copy_count: number; // 手元にある冊数
+  revision: number; // 更新のたびに増える版番号
+  created_at: string; // 登録日時 YYYY-MM-DD HH:MM:SS
+  updated_at: string; // 最後に更新した日時
   audience?: string | null;
   form?: string | null;
   content?: string | null;
 }
 
+// 書籍一覧の並び順（バックエンドの BookSort と対応、日時は新しい順）
+export type BookSort = 'title' | 'author' | 'created_at' | 'updated_at';
+
+// 一覧を登録日・更新日で絞り込む（日付は YYYY-MM-DD、両端を含む）
+export interface BookDateFilter {
+  field: 'created_at' | 'updated_at';
+  from?: string | null;
+  to?: string | null;
+}
+
+// 本に複数付けられるタグ
+export interface Tag {
+  id: number;
+  name: string;
+}
+
 // Genreの型もついでにこちらに移動しておくと、さらに管理しやすくなります
 export interface Genre {
   id: number;
   name: string;
+  parent_id: number | null;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface GenreNode {
+  id: number;
+  name: string;
+  parent_id: number | null;
+  book_count: number;
+  total_book_count: number;
+  children: GenreNode[];
 }
 
 export interface NewBook {
   title: string;
+  title_kana?: string;
   genre_id: number | null;
   isbn?: string;
   author?: string;
+  author_kana?: string;
   publisher?: string;
   price?: number;
   c_code?: string;      // ← 追加
   is_read?: number; // 省略時 0
+  series?: string;
+  volume?: number;
+  location_id?: number | null; // 1冊目の置き場所
+  allow_duplicate?: boolean; // true なら重複候補があっても登録する
 }
 
 export interface UpdateBook {
   id: number;
   title: string;
+  title_kana?: string | null;
   isbn?: string;
   author?: string;
+  author_kana?: string | null;
   publisher?: string;
   price?: number;
   c_code?: string;
   is_read: number;
   genre_id?: number | null; // 'null' を追加
+  revision?: number; // 読み込んだときの版番号。他で更新されていれば保存しない
+}
+
+// 本の一部の項目だけを変える（省略した項目は変えない、null は値を外す）
+export interface BookPatch {
+  id: number;
+  revision: number;
+  title?: string;
+  title_kana?: string | null;
+  isbn?: string | null;
+  author?: string | null;
+  author_kana?: string | null;
+  publisher?: string | null;
+  price?: number | null;
+  c_code?: string | null;
+  genre_id?: number | null;
+  is_read?: boolean;
+}
+
+// 選択した本にまとめて適用する変更（省略した項目は変えない、null は値を外す）
+export interface BulkBookPatch {
+  genre_id?: number | null;
+  is_read?: boolean;
+  add_tag_ids?: number[];
+  remove_tag_ids?: number[];
+  location_id?: number | null;
+  publisher?: string | null;
 }
 
 export interface CCodeInterpretation {
@@ -54,7 +124,410 @@ export interface CCodeInterpretation {
 
 // APIから取得した書籍情報を表す型
 export interface BookInfoFromApi {
+  title: string;
+  title_kana?: string | null;
+  author: string;
+  author_kana?: string | null;
+  publisher: string;
+  series?: string | null;
+  volume?: number | null;
+  isbn?: string | null;
+  cover_url?: string | null; // download_book_cover に渡すと書影を保存できる
+}
+
+// プロバイダの検索で見つかった未所持の巻（is_newer: 所持最新巻より後の巻）
+export interface VolumeCandidate {
+  isbn: string;
   title: string;
   author: string;
   publisher: string;
+  volume: number;
+  is_newer: boolean;
+  source: 'ndl' | 'rakuten';
+}
+
+// シリーズごとの所持状況（missing_volumes は1巻から最大所持巻までの抜け）
+export interface SeriesSummary {
+  id: number;
+  title: string;
+  title_kana?: string | null;
+  book_count: number;
+  owned_volumes: number[];
+  missing_volumes: number[];
+}
+
+export interface SeriesVolume {
+  volume?: number | null;
+  book: Book;
+}
+
+// rename_genre / merge_genres などが返す型付きエラー（duplicate は add_book が重複候補を返す）
+export type CommandError =
+  | { kind: 'conflict' | 'not_found' | 'invalid' | 'database'; message: string }
+  | { kind: 'duplicate'; message: DuplicateMatch[] };
+
+export type ReadingStatus = 'reading' | 'finished' | 'abandoned';
+
+// 1回分の読書記録（日付は YYYY-MM-DD）。is_read は読了記録の有無から導かれる
+export interface ReadingSession {
+  id: number;
+  book_id: number;
+  status: ReadingStatus;
+  started_at?: string | null;
+  finished_at?: string | null;
+  current_page?: number | null;
+  total_pages?: number | null;
+}
+
+export interface CurrentReading {
+  session: ReadingSession;
+  book: Book;
+}
+
+export interface BookReadCount {
+  book_id: number;
+  read_count: number;
+}
+
+// 期間ごとの読了冊数と読んだページ数（period は YYYY-MM または YYYY）
+export interface PeriodStat {
+  period: string;
+  finished_count: number;
+  pages_read: number;
+}
+
+export interface RankedCount {
+  name: string;
+  count: number;
+}
+
+export interface ReadingStats {
+  from?: string | null;
+  to?: string | null;
+  finished_count: number;
+  pages_read: number;
+  by_month: PeriodStat[];
+  by_year: PeriodStat[];
+  top_authors: RankedCount[];
+  top_publishers: RankedCount[];
+  top_genres: RankedCount[];
+  average_days_to_finish?: number | null;
+  backlog_count: number;
+  backlog_price: number;
+}
+
+export interface BookReview {
+  book_id: number;
+  rating?: number | null;
+  review?: string | null;
+}
+
+export type NoteKind = 'note' | 'quote';
+
+// 本に付けるメモ・引用（page はページ番号）
+export interface BookNote {
+  id: number;
+  book_id: number;
+  kind: NoteKind;
+  body: string;
+  page?: number | null;
+  created_at: string;
+}
+
+export interface NewBookNote {
+  book_id: number;
+  kind: NoteKind;
+  body: string;
+  page?: number | null;
+}
+
+export interface UpdateBookNote {
+  id: number;
+  kind: NoteKind;
+  body: string;
+  page?: number | null;
+}
+
+// 貸出記録（日付は YYYY-MM-DD、返却前は returned_at が null）
+export interface Loan {
+  id: number;
+  book_id: number;
+  copy_id: number;
+  borrower: string;
+  lent_at: string;
+  due_at?: string | null;
+  returned_at?: string | null;
+}
+
+export interface OutstandingLoan {
+  loan: Loan;
+  book: Book;
+  is_overdue: boolean;
+}
+
+export type LocationKind = 'building' | 'room' | 'shelf';
+
+// 置き場所（建物 > 部屋 > 棚）。path は「本社 > 資料室 > 棚A」のような表示名
+export interface Location {
+  id: number;
+  name: string;
+  kind: LocationKind;
+  parent_id: number | null;
+  path: string;
+}
+
+export type AcquisitionKind = 'new' | 'used' | 'gift';
+
+// 手元の1冊（同じ本を複数持っていれば冊ごとに1件）
+export interface Copy {
+  id: number;
+  book_id: number;
+  condition?: string | null;
+  acquired_at?: string | null;
+  purchase_price?: number | null; // 実際に払った金額（price は定価）
+  store?: string | null;
+  acquisition?: AcquisitionKind | null;
+  location_id?: number | null;
+  notes?: string | null;
+}
+
+export interface NewCopy {
+  book_id: number;
+  condition?: string | null;
+  acquired_at?: string | null; // 省略時は今日
+  purchase_price?: number | null;
+  store?: string | null;
+  acquisition?: AcquisitionKind | null;
+  location_id?: number | null;
+  notes?: string | null;
+}
+
+export interface UpdateCopy {
+  id: number;
+  condition?: string | null;
+  acquired_at?: string | null;
+  purchase_price?: number | null;
+  store?: string | null;
+  acquisition?: AcquisitionKind | null;
+  location_id?: number | null;
+  notes?: string | null;
+}
+
+export interface CopyLocation {
+  book: Book;
+  copy: Copy;
+  location?: Location | null;
+}
+
+export interface StocktakeResult {
+  location_id: number;
+  found: CopyLocation[];
+  missing: CopyLocation[];
+  misplaced: CopyLocation[];
+  unknown_isbns: string[];
+}
+
+// 1冊の購入記録（record_purchase に渡す）
+export interface Purchase {
+  copy_id: number;
+  acquired_at?: string | null; // 省略時は今日
+  purchase_price?: number | null;
+  store?: string | null;
+  acquisition: AcquisitionKind;
+}
+
+export interface PeriodSpending {
+  period: string;
+  spent: number;
+  copy_count: number;
+}
+
+export interface AcquisitionSpending {
+  acquisition?: AcquisitionKind | null; // null は種類の記録が無い冊
+  spent: number;
+  copy_count: number;
+}
+
+export interface SpendingSummary {
+  from?: string | null;
+  to?: string | null;
+  total_spent: number;
+  copy_count: number;
+  by_month: PeriodSpending[];
+  by_year: PeriodSpending[];
+  by_acquisition: AcquisitionSpending[];
+  top_stores: RankedCount[];
+}
+
+// 欲しいものリストの1件（priority は 1 が最優先、target_price はこの値段以下なら買う目安）
+export interface WishlistItem {
+  id: number;
+  isbn?: string | null;
+  title: string;
+  title_kana?: string | null;
+  author?: string | null;
+  author_kana?: string | null;
+  publisher?: string | null;
+  price?: number | null;
+  c_code?: string | null;
+  series?: string | null;
+  volume?: number | null;
+  priority: number;
+  target_price?: number | null;
+  notes?: string | null;
+  added_at: string;
+}
+
+export interface NewWishlistItem {
+  isbn?: string | null;
+  title: string;
+  title_kana?: string | null;
+  author?: string | null;
+  author_kana?: string | null;
+  publisher?: string | null;
+  price?: number | null;
+  c_code?: string | null;
+  series?: string | null;
+  volume?: number | null;
+  priority?: number; // 省略時 3
+  target_price?: number | null;
+  notes?: string | null;
+}
+
+export interface UpdateWishlistItem extends Omit<NewWishlistItem, 'priority'> {
+  id: number;
+  priority: number;
+}
+
+// 買った本を蔵書に移すときの内容（purchase_wishlist_item に渡す）
+export interface PurchaseWishlistItem {
+  id: number;
+  genre_id?: number | null;
+  location_id?: number | null;
+  acquired_at?: string | null;
+  purchase_price?: number | null;
+  store?: string | null;
+  acquisition?: AcquisitionKind | null;
+}
+
+export type DuplicateReason = 'isbn' | 'title_author';
+
+export interface DuplicateMatch {
+  book: Book;
+  reason: DuplicateReason;
+}
+
+export interface DuplicateGroup {
+  reason: DuplicateReason;
+  books: Book[];
+}
+
+export type BookField =
+  | 'isbn' | 'title' | 'title_kana' | 'author' | 'author_kana' | 'publisher'
+  | 'price' | 'c_code' | 'genre_id' | 'rating' | 'review' | 'series';
+
+// source の本を target に統合する（take_from_source に挙げた項目は source の値を使う）
+export interface MergeBooks {
+  target_id: number;
+  source_id: number;
+  take_from_source?: BookField[];
+}
+
+export interface BackupInfo {
+  file_name: string;
+  created_at: string; // "YYYY-MM-DD HH:MM:SS"
+  size: number;
+  schema_version: number | null;
+}
+
+export interface ArchiveManifest {
+  format: string;
+  format_version: number;
+  app_version: string;
+  schema_version: number;
+  exported_at: string;
+  book_count: number;
+  cover_count: number;
+}
+
+// replace: 今のライブラリを置き換える / merge: 無い本だけを追加する
+export type ImportMode = 'replace' | 'merge';
+
+export interface ImportResult {
+  manifest: ArchiveManifest;
+  books_added: number;
+  books_skipped: number;
+  covers_imported: number;
+  settings: Record<string, string>; // エクスポート元の localStorage の内容
+}
+
+export type HistoryAction = 'insert' | 'update' | 'delete';
+
+// before / after は変更前後のスナップショット（テーブル名ごとの行の配列）
+export interface BookHistoryEntry {
+  id: number;
+  book_id: number;
+  action: HistoryAction;
+  title: string;
+  before: Record<string, Record<string, unknown>[]> | null;
+  after: Record<string, Record<string, unknown>[]> | null;
+  changed_at: string;
+  undone_at: string | null;
+}
+
+export interface TrashedBook {
+  book: Book;
+  deleted_at: string;
+  purge_on: string;
+}
+
+// ISBN で書誌情報を引く先
+export type MetadataProvider = 'ndl' | 'google' | 'rakuten';
+
+// 書誌情報の取得設定（priority が空なら NDL・Google Books・楽天ブックスの順）
+export interface ProviderSettings {
+  priority: MetadataProvider[];
+  google_api_key?: string | null;
+  rakuten_application_id?: string | null;
+}
+
+// 取り直した書誌情報のうち、登録内容と異なる項目
+export interface MetadataChange {
+  field: BookField;
+  current: string | null;
+  fetched: string;
+}
+
+export interface MetadataRefresh {
+  book_id: number;
+  title: string;
+  revision: number; // apply_book_metadata にそのまま渡す
+  changes: MetadataChange[];
+  error: string | null;
+}
+
+// 採用する項目と値
+export interface AcceptedMetadata {
+  book_id: number;
+  revision: number;
+  fields: Partial<Record<BookField, string>>;
+}
+
+// metadata-fill-progress イベント
+export interface MetadataFillProgress {
+  processed: number;
+  total: number;
+  book_id: number;
+  title: string;
+  filled: BookField[];
+  error: string | null;
+}
+
+// metadata-fill-finished イベント
+export interface MetadataFillSummary {
+  total: number;
+  processed: number;
+  updated: number;
+  failed: number;
+  cancelled: boolean;
 }